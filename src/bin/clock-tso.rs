@@ -0,0 +1,69 @@
+//! Runs a timestamp oracle (see `clock::tso`) in the foreground.
+//!
+//! Usage: `clock-tso --listen <host:port | unix:path> --lease <path> [--lease-window-ms <ms>]
+//! [--ntp]`
+//!
+//! The oracle reads the system clock. With `--ntp`, it also queries pool.ntp.org about once a
+//! minute, which stalls every client until the server replies or the query times out.
+
+use clock::hybrid_logical_clock::{HybridLogicalClock, SystemClock};
+use clock::tso::{Address, Oracle, Server};
+use std::process::ExitCode;
+use std::time::Duration;
+
+const USAGE: &str = "usage: clock-tso --listen <host:port | unix:path> --lease <path> \
+                     [--lease-window-ms <ms>] [--ntp]";
+
+fn run() -> Result<(), String> {
+    let mut listen = None;
+    let mut lease = None;
+    let mut lease_window = Duration::from_secs(3);
+    let mut ntp = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--listen" => listen = Some(value()?),
+            "--lease" => lease = Some(value()?),
+            "--lease-window-ms" => {
+                let ms = value()?;
+                let ms = ms
+                    .parse()
+                    .map_err(|_| format!("invalid lease window: {ms}"))?;
+                lease_window = Duration::from_millis(ms);
+            }
+            "--ntp" => ntp = true,
+            _ => return Err(format!("unexpected argument: {arg}")),
+        }
+    }
+    let (Some(listen), Some(lease)) = (listen, lease) else {
+        return Err(USAGE.to_string());
+    };
+
+    let clock = if ntp {
+        HybridLogicalClock::new()
+    } else {
+        HybridLogicalClock::new().with_time_source(SystemClock)
+    };
+    let address: Address = listen.parse().map_err(|e| format!("{e}"))?;
+    let oracle = Oracle::open(clock, lease, lease_window).map_err(|e| format!("{e}"))?;
+    let server = Server::bind(&address, oracle)
+        .map_err(|e| format!("{e}"))?
+        .with_accept_error_handler(|e| eprintln!("clock-tso: failed to accept a client: {e}"));
+    eprintln!("clock-tso listening on {}", server.address());
+    server.serve().map_err(|e| format!("{e}"))
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("clock-tso: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Command-line tools for working with logical clocks.
+//!
+//! Usage:
+//! - `clock merge [--field <name>] <file>...`: merges JSON-lines log files, each sorted by the HLC
+//!   timestamp in the given field (`hlc` by default), into one stream on standard output. A
+//!   timestamp is either a packed (microsecond-resolution) integer or an `{"l": .., "c": ..}`
+//!   object. Entries that are out of order within their own file are reported on standard error.
+//! - `clock export [--format shiviz|dot] <file>`: converts a JSON-lines file of events, each an
+//!   object like `{"process": "p", "event": "send", "clock": {"p": 1, "q": 2}}`, into a ShiViz log
+//!   (the default) or a Graphviz space-time diagram on standard output. Process names mustn't be
+//!   empty or contain whitespace.
+
+use clock::export::Execution;
+use clock::hybrid_logical_clock::{HlcTimestamp, Micros, Resolution};
+use clock::merge::merge_by_hlc;
+use clock::vector_clock::VectorClock;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: clock merge [--field <name>] <file>...
+       clock export [--format shiviz|dot] <file>";
+
+/// Reads the HLC timestamp in `field` of a JSON log line.
+fn parse_timestamp(line: &str, field: &str) -> Result<HlcTimestamp, String> {
+    let entry: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    match entry.get(field) {
+        Some(Value::Number(packed)) => packed
+            .as_u64()
+            .map(HlcTimestamp::unpack)
+            .ok_or_else(|| format!("invalid packed timestamp: {packed}")),
+        Some(Value::Object(timestamp)) => {
+            let component = |name| timestamp.get(name).and_then(Value::as_u64);
+            match (component("l"), component("c").map(u16::try_from)) {
+                (Some(l), Some(Ok(c))) if c <= Micros::MAX_COUNTER => Ok(HlcTimestamp::new(l, c)),
+                _ => Err(format!("invalid timestamp: {}", entry[field])),
+            }
+        }
+        Some(other) => Err(format!("invalid timestamp: {other}")),
+        None => Err(format!("missing field {field:?}")),
+    }
+}
+
+fn merge(args: &[String]) -> Result<bool, String> {
+    let mut field = "hlc".to_string();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--field" => field = args.next().ok_or(USAGE)?.clone(),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    // The first error hit while reading any of the files, which ends the merge.
+    let error = RefCell::new(None);
+    let mut streams = Vec::new();
+    for path in &paths {
+        let file = File::open(path).map_err(|e| format!("{path}: {e}"))?;
+        let (error, field) = (&error, &field);
+        let stream = BufReader::new(file)
+            .lines()
+            .enumerate()
+            .map_while(move |(index, line)| {
+                let parsed = line
+                    .map_err(|e| e.to_string())
+                    .and_then(|line| Ok((parse_timestamp(&line, field)?, (index + 1, line))));
+                parsed
+                    .map_err(|e| *error.borrow_mut() = Some(format!("{path}:{}: {e}", index + 1)))
+                    .ok()
+            });
+        streams.push(stream);
+    }
+
+    let mut in_order = true;
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    for entry in merge_by_hlc(streams) {
+        if let Some(e) = error.borrow_mut().take() {
+            return Err(e);
+        }
+        match entry {
+            Ok(entry) => writeln!(stdout, "{}", entry.value.1).map_err(|e| e.to_string())?,
+            Err(entry) => {
+                in_order = false;
+                eprintln!(
+                    "{}:{}: timestamp {} precedes that of the previous entry, {}",
+                    paths[entry.stream], entry.value.0, entry.timestamp, entry.previous
+                );
+            }
+        }
+    }
+    if let Some(e) = error.borrow_mut().take() {
+        return Err(e);
+    }
+    stdout.flush().map_err(|e| e.to_string())?;
+    Ok(in_order)
+}
+
+/// Reads an event of an execution from a JSON log line.
+fn parse_event(line: &str) -> Result<(String, String, VectorClock<String, u64>), String> {
+    let entry: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let text = |field: &str| match entry.get(field) {
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(Value::Number(n)) => Ok(n.to_string()),
+        Some(other) => Err(format!("invalid {field}: {other}")),
+        None => Err(format!("missing field {field:?}")),
+    };
+    let (process, event) = (text("process")?, text("event")?);
+    // ShiViz reads a host up to the first whitespace, so such a name would be split.
+    if process.is_empty() || process.contains(char::is_whitespace) {
+        return Err(format!("invalid process name: {process:?}"));
+    }
+    let Some(Value::Object(entries)) = entry.get("clock") else {
+        return Err("missing or invalid field \"clock\"".to_string());
+    };
+    let entries = entries
+        .iter()
+        .map(|(k, v)| match v.as_u64() {
+            Some(v) => Ok((k.clone(), v)),
+            None => Err(format!("invalid clock entry: {k:?}: {v}")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let clock = VectorClock::from_entries(process.clone(), entries);
+    Ok((process, event, clock))
+}
+
+fn export(args: &[String]) -> Result<bool, String> {
+    let mut dot = false;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("shiviz") => dot = false,
+                Some("dot") => dot = true,
+                _ => return Err(USAGE.to_string()),
+            },
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let path = path.ok_or(USAGE)?;
+
+    let file = File::open(path).map_err(|e| format!("{path}: {e}"))?;
+    let mut records = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let record = line
+            .map_err(|e| e.to_string())
+            .and_then(|line| parse_event(&line));
+        records.push(record.map_err(|e| format!("{path}:{}: {e}", index + 1))?);
+    }
+    let execution = Execution::new(records).map_err(|e| {
+        format!(
+            "{path}: two events of process {} have the same clock entry",
+            e.0
+        )
+    })?;
+    let output = if dot {
+        execution.to_dot()
+    } else {
+        execution.to_shiviz()
+    };
+    io::stdout()
+        .lock()
+        .write_all(output.as_bytes())
+        .map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("merge") => merge(&args[1..]),
+        Some("export") => export(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("clock: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Causal broadcast: every process delivers every broadcast message only after all messages that
+//! causally precede it, whatever order the network hands them over in.
+//!
+//! Each message carries the vector clock of its broadcast, counting *broadcasts* only: entry `k`
+//! is the number of messages from `k` that the sender had delivered (or sent) beforehand. A
+//! message from `j` is then deliverable once the receiver has delivered exactly `clock[j] - 1`
+//! messages from `j`, and at least `clock[k]` messages from every other `k`.
+//!
+//! See "Lightweight Causal and Atomic Group Multicast" by Birman, Schiper and Stephenson.
+
+use crate::vector_clock::VectorClock;
+use num_traits::CheckedAdd;
+use std::fmt;
+use std::hash::Hash;
+
+/// A broadcast message, as received and as delivered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message<K, V, T>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    pub sender: K,
+    pub clock: VectorClock<K, V>,
+    pub payload: T,
+}
+
+/// The messages delivered as a result of receiving one, in causal order.
+pub type Deliveries<K, V, T> = Vec<Message<K, V, T>>;
+
+/// The error returned when a message can't be delivered yet, and there's no room left to hold it
+/// back. The message is handed back, e.g. to be dropped and retransmitted later.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Full<K, V, T>(pub Message<K, V, T>)
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone;
+
+impl<K, V, T> fmt::Display for Full<K, V, T>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "causal delivery queue is full")
+    }
+}
+
+impl<K, V, T> std::error::Error for Full<K, V, T>
+where
+    K: Eq + Hash + Clone + fmt::Debug,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone + fmt::Debug,
+    T: fmt::Debug,
+{
+}
+
+/// Holds back received messages until they can be delivered in causal order.
+#[derive(Clone, Debug)]
+pub struct CausalDeliveryQueue<T, K = usize, V = usize>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    /// Entry `k` is the number of messages from `k` delivered so far (including the owning
+    /// process's own broadcasts).
+    delivered: VectorClock<K, V>,
+    /// Messages that were received, but can't be delivered yet, in the order they arrived.
+    pending: Vec<Message<K, V, T>>,
+    capacity: usize,
+}
+
+impl<T, K, V> CausalDeliveryQueue<T, K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    /// Constructs the delivery queue of process `i`, holding back at most `capacity` messages.
+    pub fn new(i: K, capacity: usize) -> Self {
+        Self {
+            delivered: VectorClock::new(i),
+            pending: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Returns the clock to broadcast a message with, and counts the message as delivered to the
+    /// owning process itself.
+    pub fn broadcast(&mut self) -> VectorClock<K, V> {
+        self.delivered.bump();
+        self.delivered.clone()
+    }
+
+    /// Returns the number of messages delivered so far from each process.
+    pub fn delivered(&self) -> &VectorClock<K, V> {
+        &self.delivered
+    }
+
+    /// Returns the messages being held back, in the order they were received.
+    pub fn pending(&self) -> &[Message<K, V, T>] {
+        &self.pending
+    }
+
+    /// Returns, for each process whose messages are holding back others, the number of the next
+    /// message from it that has to be delivered (and hasn't been received yet).
+    pub fn blocking(&self) -> Vec<(&K, V)> {
+        let mut blocking: Vec<(&K, V)> = Vec::new();
+        for message in &self.pending {
+            for (k, v) in message.clock.iter() {
+                let next = self.next(k);
+                let needed = if *k == message.sender {
+                    *v > next
+                } else {
+                    *v >= next
+                };
+                let received = self
+                    .pending
+                    .iter()
+                    .any(|other| other.sender == *k && other.clock.get(k) == next);
+                if needed && !received && !blocking.iter().any(|(b, _)| *b == k) {
+                    blocking.push((k, next));
+                }
+            }
+        }
+        blocking
+    }
+
+    /// Receives a message broadcast by `sender`, and returns the messages that can be delivered as
+    /// a result, in causal order. Messages that were already delivered are dropped. Fails, handing
+    /// the message back, if it can't be delivered yet and the queue is full.
+    pub fn receive(
+        &mut self,
+        sender: K,
+        clock: VectorClock<K, V>,
+        payload: T,
+    ) -> Result<Deliveries<K, V, T>, Full<K, V, T>> {
+        let message = Message {
+            sender,
+            clock,
+            payload,
+        };
+        let seq = message.clock.get(&message.sender);
+        let duplicate = seq < self.next(&message.sender)
+            || self.pending.iter().any(|other| {
+                other.sender == message.sender && other.clock.get(&other.sender) == seq
+            });
+        if duplicate {
+            return Ok(Vec::new());
+        }
+        if !self.is_deliverable(&message) {
+            if self.pending.len() >= self.capacity {
+                return Err(Full(message));
+            }
+            self.pending.push(message);
+            return Ok(Vec::new());
+        }
+
+        let mut delivered = vec![self.deliver(message)];
+        while let Some(n) = self.pending.iter().position(|m| self.is_deliverable(m)) {
+            let message = self.pending.remove(n);
+            delivered.push(self.deliver(message));
+        }
+        Ok(delivered)
+    }
+
+    /// The number of the next message from `k` to be delivered.
+    fn next(&self, k: &K) -> V {
+        self.delivered
+            .get(k)
+            .checked_add(&V::from(1))
+            .expect("vector clock entry overflowed")
+    }
+
+    fn is_deliverable(&self, message: &Message<K, V, T>) -> bool {
+        message.clock.iter().all(|(k, v)| {
+            if *k == message.sender {
+                *v == self.next(k)
+            } else {
+                *v <= self.delivered.get(k)
+            }
+        })
+    }
+
+    fn deliver(&mut self, message: Message<K, V, T>) -> Message<K, V, T> {
+        self.delivered.merge(&message.clock);
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Rng;
+
+    /// Simulates processes that broadcast over a network delivering messages in random order.
+    fn simulate(seed: u64, processes: usize, broadcasts: usize) {
+        let mut rng = Rng::new(seed);
+        let mut queues: Vec<_> = (0..processes)
+            .map(|i| CausalDeliveryQueue::<(usize, usize)>::new(i, usize::MAX))
+            .collect();
+        // Each process's deliveries, as (sender, clock) pairs.
+        let mut logs: Vec<Vec<(usize, VectorClock)>> = vec![Vec::new(); processes];
+        // Messages in flight, as (destination, sender, clock, payload).
+        let mut network = Vec::new();
+        let mut sent = 0;
+
+        while sent < broadcasts || !network.is_empty() {
+            if sent < broadcasts && (network.is_empty() || rng.below(3) == 0) {
+                let sender = rng.below(processes);
+                let clock = queues[sender].broadcast();
+                logs[sender].push((sender, clock.clone()));
+                for to in (0..processes).filter(|to| *to != sender) {
+                    network.push((to, sender, clock.clone(), (sender, sent)));
+                }
+                sent += 1;
+            } else {
+                let (to, sender, clock, payload) = rng.take(&mut network);
+                for message in queues[to].receive(sender, clock, payload).unwrap() {
+                    logs[to].push((message.sender, message.clock));
+                }
+            }
+        }
+
+        for (queue, log) in queues.iter().zip(&logs) {
+            assert!(queue.pending().is_empty(), "seed {seed}");
+            assert_eq!(log.len(), broadcasts, "seed {seed}");
+            // Nothing is delivered before a message that causally precedes it.
+            for (n, (_, later)) in log.iter().enumerate() {
+                for (_, earlier) in &log[n + 1..] {
+                    assert!(!earlier.happens_before(later), "seed {seed}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_reorderings() {
+        for seed in 1..=200 {
+            simulate(seed, 4, 30);
+        }
+    }
+
+    #[test]
+    fn test_blocking() {
+        let [mut p0, mut p1] = [0, 1].map(|i| CausalDeliveryQueue::<&str>::new(i, 1));
+        let mut p2 = CausalDeliveryQueue::<&str>::new(2, 1);
+        let first = p0.broadcast();
+        let second = p0.broadcast();
+        p1.receive(0, first.clone(), "first").unwrap();
+        let reply = p1.broadcast();
+
+        // The reply depends on p0's first message, which hasn't reached p2 yet.
+        assert_eq!(p2.receive(1, reply.clone(), "reply"), Ok(Vec::new()));
+        assert_eq!(p2.blocking(), [(&0, 1)]);
+        assert_eq!(
+            p2.receive(0, second.clone(), "second"),
+            Err(Full(Message {
+                sender: 0,
+                clock: second.clone(),
+                payload: "second",
+            }))
+        );
+
+        let delivered = p2.receive(0, first.clone(), "first").unwrap();
+        let payloads: Vec<_> = delivered.iter().map(|m| m.payload).collect();
+        assert_eq!(payloads, ["first", "reply"]);
+        assert!(p2.blocking().is_empty());
+        // Duplicates are dropped.
+        assert_eq!(p2.receive(0, first, "first"), Ok(Vec::new()));
+        assert_eq!(p2.receive(0, second, "second").unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,133 @@
+//! Unsigned LEB128 varints: seven bits per byte, least significant group first, with the high bit
+//! of each byte set if more bytes follow. Small integers, which clock entries overwhelmingly are,
+//! take a single byte.
+
+use std::fmt;
+
+/// The most bytes a varint-encoded `u64` can take.
+pub const MAX_VARINT_LEN: usize = 10;
+
+/// Integers that can be written as varints.
+pub trait Varint: Copy {
+    fn to_u64(self) -> u64;
+
+    /// Returns `None` if `value` doesn't fit into `Self`.
+    fn from_u64(value: u64) -> Option<Self>;
+}
+
+macro_rules! impl_varint {
+    ($($t:ty),*) => {
+        $(
+            impl Varint for $t {
+                fn to_u64(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_u64(value: u64) -> Option<Self> {
+                    Self::try_from(value).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_varint!(u8, u16, u32, u64, usize);
+
+/// An error decoding bytes produced by one of the crate's encoders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended in the middle of a value.
+    Truncated,
+    /// A value doesn't fit into the type it's decoded into.
+    Overflow,
+    /// The input is well-formed, but isn't something the encoder produces (e.g. trailing bytes).
+    Invalid,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "input is truncated"),
+            DecodeError::Overflow => write!(f, "value is out of range"),
+            DecodeError::Invalid => write!(f, "input is not canonically encoded"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Appends `value` to `buf` as a varint.
+pub fn write_varint<T: Varint>(buf: &mut Vec<u8>, value: T) {
+    let mut value = value.to_u64();
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Reads a varint off the front of `buf`, advancing it past the bytes read.
+pub fn read_varint<T: Varint>(buf: &mut &[u8]) -> Result<T, DecodeError> {
+    let mut value = 0u64;
+    for (i, &byte) in buf.iter().enumerate().take(MAX_VARINT_LEN) {
+        let bits = u64::from(byte & 0x7f);
+        // The tenth byte only has room for the top bit of a `u64`.
+        if i == MAX_VARINT_LEN - 1 && bits > 1 {
+            return Err(DecodeError::Overflow);
+        }
+        value |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            // Padding with zero-valued groups would give the same value several encodings.
+            if i > 0 && byte == 0 {
+                return Err(DecodeError::Invalid);
+            }
+            *buf = &buf[i + 1..];
+            return T::from_u64(value).ok_or(DecodeError::Overflow);
+        }
+    }
+    if buf.len() >= MAX_VARINT_LEN {
+        Err(DecodeError::Overflow)
+    } else {
+        Err(DecodeError::Truncated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        for (value, len) in [(0, 1), (127, 1), (128, 2), (16_383, 2), (u64::MAX, 10)] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value);
+            assert_eq!(buf.len(), len);
+            let mut bytes = &buf[..];
+            assert_eq!(read_varint::<u64>(&mut bytes), Ok(value));
+            assert!(bytes.is_empty());
+            assert_eq!(
+                read_varint::<u64>(&mut &buf[..len - 1]),
+                Err(DecodeError::Truncated)
+            );
+        }
+
+        assert_eq!(
+            read_varint::<u8>(&mut &[0x80, 0x02][..]),
+            Err(DecodeError::Overflow)
+        );
+        assert_eq!(
+            read_varint::<u64>(&mut &[0xff; 11][..]),
+            Err(DecodeError::Overflow)
+        );
+        let mut too_big = [0xff; 10];
+        too_big[9] = 0x02;
+        assert_eq!(
+            read_varint::<u64>(&mut &too_big[..]),
+            Err(DecodeError::Overflow)
+        );
+        assert_eq!(
+            read_varint::<u64>(&mut &[0x81, 0x00][..]),
+            Err(DecodeError::Invalid)
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! A vector clock for a fixed membership of `N` processes, identified by their index in
+//! `0..N`. Keeping the entries in one contiguous array of `u64` counters (rather than a
+//! `HashMap`, as [`VectorClock`](crate::vector_clock::VectorClock) does) makes merging and
+//! comparing single linear passes without lookups, which the compiler can vectorize.
+
+use crate::LamportClock;
+use crate::vector_clock::VectorClock;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct DenseVectorClock<const N: usize> {
+    /// `clock[j]` is the number of events this clock's process knows to have taken place at
+    /// process `j` (see [`VectorClock`]).
+    clock: [u64; N],
+    /// The index of the process who owns this clock.
+    i: usize,
+}
+
+impl<const N: usize> DenseVectorClock<N> {
+    /// Constructs a new vector clock for the process with the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` isn't in `0..N`.
+    pub fn new(i: usize) -> Self {
+        assert!(
+            i < N,
+            "process index {i} is out of bounds for {N} processes"
+        );
+        Self { clock: [0; N], i }
+    }
+
+    /// Increments the owning process's entry.
+    pub fn bump(&mut self) {
+        self.clock[self.i] += 1;
+    }
+
+    /// Returns whether this clock's event happens before the other's.
+    #[inline]
+    pub fn happens_before(&self, other: &Self) -> bool {
+        self < other
+    }
+
+    /// Returns whether neither this clock's event nor the other's happens before the other.
+    #[inline]
+    pub fn is_concurrent_with(&self, other: &Self) -> bool {
+        self.partial_cmp(other).is_none()
+    }
+
+    /// Bumps the clock, and returns a copy of it to be piggybacked onto a message.
+    #[inline]
+    pub fn send(&mut self) -> Self {
+        self.bump();
+        self.clone()
+    }
+
+    /// Merges the clock piggybacked onto a received message into this one, and bumps it.
+    #[inline]
+    pub fn receive(&mut self, incoming_clock: &Self) {
+        self.merge(incoming_clock);
+        self.bump();
+    }
+
+    /// Returns the entries of the clock, indexed by process.
+    pub fn entries(&self) -> &[u64; N] {
+        &self.clock
+    }
+
+    /// Takes each entry to be the maximum between it and the other clock's.
+    fn merge(&mut self, other: &Self) {
+        for (v, other_v) in self.clock.iter_mut().zip(&other.clock) {
+            *v = (*v).max(*other_v);
+        }
+    }
+}
+
+impl<const N: usize> LamportClock for DenseVectorClock<N> {
+    fn bump(&mut self) {
+        DenseVectorClock::bump(self);
+    }
+
+    fn send(&mut self) -> Self {
+        DenseVectorClock::send(self)
+    }
+
+    fn receive(&mut self, incoming_clock: &Self) {
+        DenseVectorClock::receive(self, incoming_clock);
+    }
+}
+
+/// Clocks are equal if their entries are, whichever processes own them, like [`VectorClock`]s.
+impl<const N: usize> PartialEq for DenseVectorClock<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.clock == other.clock
+    }
+}
+
+impl<const N: usize> Eq for DenseVectorClock<N> {}
+
+impl<const N: usize> Hash for DenseVectorClock<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.clock.hash(state);
+    }
+}
+
+impl<const N: usize> PartialOrd for DenseVectorClock<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        // Accumulate without branching on the entries, so that the loop vectorizes.
+        let (mut has_less, mut has_greater) = (false, false);
+        for (v, other_v) in self.clock.iter().zip(&other.clock) {
+            has_less |= v < other_v;
+            has_greater |= v > other_v;
+        }
+        match (has_greater, has_less) {
+            (true, false) => Some(Ordering::Greater),
+            (false, true) => Some(Ordering::Less),
+            (false, false) => Some(Ordering::Equal),
+            (true, true) => None,
+        }
+    }
+}
+
+impl<const N: usize> From<DenseVectorClock<N>> for VectorClock<usize, u64> {
+    fn from(dense: DenseVectorClock<N>) -> Self {
+        let mut sparse = VectorClock::new(dense.i);
+        sparse.clock = Arc::new((0..N).zip(dense.clock).filter(|(_, v)| *v != 0).collect());
+        sparse
+    }
+}
+
+/// The error returned when converting a sparse vector clock with an entry (or owner) outside of
+/// `0..N` into a [`DenseVectorClock<N>`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutOfBounds(pub usize);
+
+impl std::fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "process index {} is out of bounds", self.0)
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+impl<const N: usize> TryFrom<VectorClock<usize, u64>> for DenseVectorClock<N> {
+    type Error = OutOfBounds;
+
+    fn try_from(sparse: VectorClock<usize, u64>) -> Result<Self, Self::Error> {
+        if sparse.i >= N {
+            return Err(OutOfBounds(sparse.i));
+        }
+        let mut dense = Self::new(sparse.i);
+        for (&k, &v) in sparse.clock.iter() {
+            match dense.clock.get_mut(k) {
+                Some(entry) => *entry = v,
+                // Entries holding the default value are as good as absent.
+                None if v == 0 => {}
+                None => return Err(OutOfBounds(k)),
+            }
+        }
+        Ok(dense)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_causality() {
+        let [mut vc0, mut vc1, mut vc2] = [0, 1, 2].map(DenseVectorClock::<3>::new);
+        vc0.bump();
+        vc1.receive(&vc0.send());
+        vc2.bump();
+        assert!(vc0.happens_before(&vc1));
+        assert!(vc2.is_concurrent_with(&vc0));
+        assert!(vc2.is_concurrent_with(&vc1));
+        assert_eq!(vc1.entries(), &[2, 1, 0]);
+
+        vc2.receive(&vc1.send());
+        assert!(vc1.happens_before(&vc2));
+        assert!(vc0.happens_before(&vc2));
+        assert_eq!(vc2.partial_cmp(&vc2.clone()), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn test_equality_ignores_owner() {
+        let mut vc0 = DenseVectorClock::<2>::new(0);
+        let mut vc1 = DenseVectorClock::<2>::new(1);
+        vc1.receive(&vc0.send());
+        vc0.receive(&vc1.send());
+        vc1.merge(&vc0);
+        // Both have seen the same events, so they're equal, consistently with `partial_cmp`.
+        assert_eq!(vc0.partial_cmp(&vc1), Some(Ordering::Equal));
+        assert_eq!(vc0, vc1);
+
+        let hash = |clock: &DenseVectorClock<2>| {
+            let mut hasher = std::hash::DefaultHasher::new();
+            clock.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&vc0), hash(&vc1));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let mut dense = DenseVectorClock::<4>::new(1);
+        dense.receive(&DenseVectorClock::<4>::new(3).send());
+
+        let sparse = VectorClock::from(dense.clone());
+        let mut expected = VectorClock::<usize, u64>::new(1);
+        expected.receive(&VectorClock::new(3).send());
+        assert_eq!(sparse, expected);
+        assert_eq!(DenseVectorClock::try_from(sparse), Ok(dense));
+
+        let mut sparse = VectorClock::<usize, u64>::new(0);
+        sparse.receive(&VectorClock::new(4).send());
+        assert_eq!(DenseVectorClock::<4>::try_from(sparse), Err(OutOfBounds(4)));
+        assert_eq!(
+            DenseVectorClock::<4>::try_from(VectorClock::new(4)),
+            Err(OutOfBounds(4))
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Dotted version vectors track the causal history of the concurrent values (siblings) of a key
+//! in a replicated store, where clients read a key from, and write it through, any replica.
+//!
+//! A version vector per key, with an entry per replica, can't tell which siblings a write
+//! supersedes: two clients that write through the same replica with stale contexts look like one
+//! causal successor of the other, unless the replica conservatively keeps every sibling the
+//! context doesn't cover, in which case siblings pile up forever (sibling explosion). Tagging
+//! each value with the single event that wrote it, its *dot* `(replica, counter)`, separately from
+//! its causal past, makes the two cases distinguishable.
+//!
+//! [`Dvv`](crate::dotted_version_vector::Dvv) is the dotted version vector of a single value,
+//! and [`DvvSet`](crate::dotted_version_vector::DvvSet) a compact representation of all siblings
+//! of a key, sharing one entry per replica.
+//!
+//! See "Dotted Version Vectors: Logical Clocks for Optimistic Replication" by Preguiça, Baquero,
+//! Almeida, Fonte and Gonçalves, and "Scalable and Accurate Causality Tracking for Eventually
+//! Consistent Stores" by Almeida, Baquero, Gonçalves, Preguiça and Fonte.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The causal context of a read: for each replica, the number of its writes the reader has seen.
+/// Clients pass back the context of their last read with their next write.
+pub type Context<K> = HashMap<K, u64>;
+
+/// The dotted version vector of a single value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dvv<K: Eq + Hash> {
+    /// The write that produced the value: the replica it went through, and its counter there.
+    pub dot: (K, u64),
+    /// The writes the value supersedes.
+    pub context: Context<K>,
+}
+
+impl<K: Eq + Hash + Clone> Dvv<K> {
+    /// Returns the dotted version vector of a value written through `replica` with the given
+    /// context, where `siblings` are the versions the replica currently holds for the key.
+    pub fn update(siblings: &[Dvv<K>], context: &Context<K>, replica: K) -> Self {
+        let counter = siblings
+            .iter()
+            .map(|sibling| sibling.join().get(&replica).copied().unwrap_or(0))
+            .chain(context.get(&replica).copied())
+            .max()
+            .unwrap_or(0);
+        Self {
+            dot: (replica, counter + 1),
+            context: context.clone(),
+        }
+    }
+
+    /// Returns whether the given write is part of this value's causal history.
+    pub fn contains(&self, (replica, counter): &(K, u64)) -> bool {
+        self.dot == (replica.clone(), *counter)
+            || self.context.get(replica).is_some_and(|c| counter <= c)
+    }
+
+    /// Returns whether this value supersedes (or is) the other.
+    pub fn descends(&self, other: &Self) -> bool {
+        self.contains(&other.dot)
+    }
+
+    /// Returns the causal history of the value as a context, i.e. its context including its dot.
+    pub fn join(&self) -> Context<K> {
+        let mut context = self.context.clone();
+        let (replica, counter) = &self.dot;
+        let entry = context.entry(replica.clone()).or_default();
+        *entry = (*entry).max(*counter);
+        context
+    }
+}
+
+/// Merges two sets of versioned siblings, dropping those that are superseded by another.
+pub fn sync<K, T>(a: Vec<(Dvv<K>, T)>, b: Vec<(Dvv<K>, T)>) -> Vec<(Dvv<K>, T)>
+where
+    K: Eq + Hash + Clone,
+{
+    let mut siblings: Vec<(Dvv<K>, T)> = Vec::with_capacity(a.len() + b.len());
+    for (version, value) in a.into_iter().chain(b) {
+        if siblings.iter().any(|(other, _)| other.descends(&version)) {
+            continue;
+        }
+        siblings.retain(|(other, _)| !version.descends(other));
+        siblings.push((version, value));
+    }
+    siblings
+}
+
+/// The siblings written through a single replica, which are always its latest writes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Entry<T> {
+    /// The number of writes through the replica the set knows of.
+    counter: u64,
+    /// The values of the latest writes that are still siblings, oldest first, so that the last
+    /// one's dot is `(replica, counter)`.
+    values: Vec<T>,
+}
+
+impl<T> Default for Entry<T> {
+    fn default() -> Self {
+        Self {
+            counter: 0,
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<T> Entry<T> {
+    /// The counter just below the dots of the values.
+    fn floor(&self) -> u64 {
+        self.counter - self.values.len() as u64
+    }
+
+    /// Drops all but the `n` latest values.
+    fn keep_latest(&mut self, n: u64) {
+        let len = self.values.len() as u64;
+        if n < len {
+            self.values.drain(..(len - n) as usize);
+        }
+    }
+}
+
+/// All the siblings of a key, each of which is implicitly tagged with a dotted version vector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DvvSet<K: Eq + Hash, T> {
+    entries: HashMap<K, Entry<T>>,
+}
+
+impl<K: Eq + Hash, T> Default for DvvSet<K, T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, T> DvvSet<K, T> {
+    /// Constructs the set of a key that was never written.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the siblings.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.entries.values().flat_map(|entry| &entry.values)
+    }
+
+    /// Returns the causal context of the siblings, to be handed to clients reading them.
+    pub fn join(&self) -> Context<K> {
+        self.entries
+            .iter()
+            .map(|(replica, entry)| (replica.clone(), entry.counter))
+            .collect()
+    }
+
+    /// Drops the siblings that the given context has seen, i.e. that a write with that context
+    /// supersedes.
+    pub fn discard(&mut self, context: &Context<K>) {
+        for (replica, &counter) in context {
+            let entry = self.entries.entry(replica.clone()).or_default();
+            if counter >= entry.counter {
+                entry.values.clear();
+                entry.counter = counter;
+            } else {
+                entry.keep_latest(entry.counter - counter);
+            }
+        }
+    }
+
+    /// Writes a value through `replica` with the given context: superseded siblings are dropped,
+    /// and the value is added as a new one.
+    pub fn update(&mut self, context: &Context<K>, replica: K, value: T) {
+        self.discard(context);
+        let entry = self.entries.entry(replica).or_default();
+        entry.counter += 1;
+        entry.values.push(value);
+    }
+
+    /// Merges the siblings of another replica's set for the same key into this one.
+    pub fn sync(&mut self, other: &Self)
+    where
+        T: Clone,
+    {
+        for (replica, theirs) in &other.entries {
+            let Some(ours) = self.entries.get_mut(replica) else {
+                self.entries.insert(replica.clone(), theirs.clone());
+                continue;
+            };
+            // The set that has seen more writes through the replica keeps those of its values
+            // that the other set hasn't dropped.
+            if theirs.counter > ours.counter {
+                let floor = ours.floor();
+                *ours = theirs.clone();
+                ours.keep_latest(theirs.counter.saturating_sub(floor));
+            } else {
+                ours.keep_latest(ours.counter.saturating_sub(theirs.floor()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A key versioned by a single version vector with an entry per replica, which can only drop
+    /// siblings when the writer's context covers the whole vector.
+    #[derive(Default)]
+    struct VersionVectorRegister {
+        version: Context<&'static str>,
+        values: Vec<&'static str>,
+    }
+
+    impl VersionVectorRegister {
+        fn update(
+            &mut self,
+            context: &Context<&'static str>,
+            replica: &'static str,
+            value: &'static str,
+        ) {
+            let covered = self
+                .version
+                .iter()
+                .all(|(k, v)| context.get(k).is_some_and(|c| c >= v));
+            if covered {
+                self.values.clear();
+            }
+            *self.version.entry(replica).or_default() += 1;
+            self.values.push(value);
+        }
+    }
+
+    #[test]
+    fn test_sibling_explosion() {
+        // Two clients take turns writing through the same replica, each with the context it got
+        // back from its previous write, so every write supersedes that client's previous value but
+        // is concurrent with the other client's.
+        let mut register = VersionVectorRegister::default();
+        let mut set = DvvSet::new();
+        let mut siblings: Vec<(Dvv<&str>, &str)> = Vec::new();
+        let mut contexts = [Context::new(), Context::new()];
+        let mut dvv_contexts = [Context::new(), Context::new()];
+        let mut register_contexts = [Context::new(), Context::new()];
+
+        let values = ["v1", "v2", "v3", "v4", "v5", "v6"];
+        for (n, value) in values.into_iter().enumerate() {
+            let client = n % 2;
+            set.update(&contexts[client], "a", value);
+            contexts[client] = set.join();
+
+            let versions: Vec<_> = siblings.iter().map(|(v, _)| v.clone()).collect();
+            let version = Dvv::update(&versions, &dvv_contexts[client], "a");
+            dvv_contexts[client] = version.join();
+            siblings = sync(siblings, vec![(version, value)]);
+
+            register.update(&register_contexts[client], "a", value);
+            register_contexts[client] = register.version.clone();
+        }
+
+        // Only the latest write of each client remains...
+        let mut values: Vec<_> = set.values().copied().collect();
+        values.sort();
+        assert_eq!(values, ["v5", "v6"]);
+        let mut values: Vec<_> = siblings.iter().map(|(_, value)| *value).collect();
+        values.sort();
+        assert_eq!(values, ["v5", "v6"]);
+        // ...whereas the version vector can't tell which siblings were superseded.
+        assert_eq!(register.values, ["v1", "v2", "v3", "v4", "v5", "v6"]);
+
+        // A client that read both siblings supersedes both.
+        let context = set.join();
+        set.update(&context, "b", "v7");
+        assert_eq!(set.values().collect::<Vec<_>>(), [&"v7"]);
+    }
+
+    #[test]
+    fn test_sync() {
+        // Concurrent writes through different replicas are siblings once the replicas sync.
+        let (mut a, mut b) = (DvvSet::new(), DvvSet::new());
+        a.update(&Context::new(), "a", 1);
+        b.update(&Context::new(), "b", 2);
+        let mut synced = a.clone();
+        synced.sync(&b);
+        let mut values: Vec<_> = synced.values().copied().collect();
+        values.sort();
+        assert_eq!(values, [1, 2]);
+
+        // A write that has seen both supersedes them on either side of a sync.
+        let context = synced.join();
+        a.update(&context, "a", 3);
+        a.sync(&synced);
+        synced.sync(&a);
+        assert_eq!(a, synced);
+        assert_eq!(a.values().collect::<Vec<_>>(), [&3]);
+
+        // Syncing with a stale set changes nothing.
+        let stale = a.clone();
+        a.update(&a.join(), "a", 4);
+        a.sync(&stale);
+        assert_eq!(a.values().collect::<Vec<_>>(), [&4]);
+    }
+}
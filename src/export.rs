@@ -0,0 +1,227 @@
+//! Exports executions recorded as vector-clock-stamped events for visualization, either as a
+//! ShiViz log or as a Graphviz space-time diagram.
+//!
+//! ShiViz (<https://bestchai.bitbucket.io/shiviz/>) reconstructs the happens-before graph from the
+//! clocks itself. For Graphviz, messages are inferred from the clocks: an event that advances its
+//! process's knowledge of other processes received a message, sent at the latest of the newly
+//! known events that none of the others happen after.
+
+use crate::trace::{self, DuplicateEvent};
+use crate::vector_clock::VectorClock;
+use num_traits::CheckedAdd;
+use std::fmt::Display;
+use std::hash::Hash;
+
+/// The regular expression ShiViz parses the events of a log with.
+pub const SHIVIZ_REGEX: &str = r"(?<host>\S*) (?<clock>{.*})\n(?<event>.*)";
+
+/// An event, as recorded by the process it happened on.
+#[derive(Clone, Debug)]
+struct Event<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    process: K,
+    label: String,
+    clock: VectorClock<K, V>,
+}
+
+/// A recorded execution: a sequence of events, each with the process it happened on, a label and
+/// its vector clock.
+#[derive(Clone, Debug)]
+pub struct Execution<K, V = usize>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    /// The events, in the order they were recorded.
+    events: Vec<Event<K, V>>,
+    /// The processes, in order, each with its events (by index into `events`) in the order they
+    /// happened.
+    processes: Vec<(K, Vec<usize>)>,
+}
+
+impl<K, V> Execution<K, V>
+where
+    K: Ord + Hash + Clone + Display,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone + Display,
+{
+    /// Records the given events. The events of each process are ordered by their clocks' entries
+    /// of the process, so they may be given in any order, but no two of them may share an entry.
+    pub fn new(
+        records: impl IntoIterator<Item = (K, impl Display, VectorClock<K, V>)>,
+    ) -> Result<Self, DuplicateEvent<K>> {
+        let events: Vec<_> = records
+            .into_iter()
+            .map(|(process, label, clock)| Event {
+                process,
+                label: label.to_string(),
+                clock,
+            })
+            .collect();
+
+        let processes = trace::by_process(events.iter().enumerate(), |(_, event)| {
+            (&event.process, &event.clock)
+        })?
+        .into_iter()
+        .map(|(k, events)| (k, events.into_iter().map(|(n, _)| n).collect()))
+        .collect();
+        Ok(Self { events, processes })
+    }
+
+    /// Returns the execution as a ShiViz log: the parsing regular expression, an empty line (for
+    /// the default delimiter between executions), then every event in the order recorded, as
+    /// `host {"host": n, ...}` followed by its label. Line breaks in labels are replaced with
+    /// spaces, and processes mustn't contain whitespace.
+    pub fn to_shiviz(&self) -> String {
+        let mut log = format!("{SHIVIZ_REGEX}\n\n");
+        for event in &self.events {
+            let entries: Vec<_> = event
+                .clock
+                .entries_sorted()
+                .into_iter()
+                .map(|(k, v)| format!("{}: {v}", json_string(&k.to_string())))
+                .collect();
+            let label = event.label.replace(['\r', '\n'], " ");
+            log += &format!("{} {{{}}}\n{label}\n", event.process, entries.join(", "));
+        }
+        log
+    }
+
+    /// Returns the execution as a Graphviz space-time diagram: a column of events per process,
+    /// with an edge for every message inferred from the clocks.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n    node [shape=box];\n");
+        for (p, (process, indices)) in self.processes.iter().enumerate() {
+            dot += &format!("    subgraph cluster_{p} {{\n");
+            dot += &format!("        label={};\n", dot_string(&process.to_string()));
+            for &n in indices {
+                dot += &format!(
+                    "        e{n} [label={}];\n",
+                    dot_string(&self.events[n].label)
+                );
+            }
+            for pair in indices.windows(2) {
+                dot += &format!("        e{} -> e{};\n", pair[0], pair[1]);
+            }
+            dot += "    }\n";
+        }
+        for (from, to) in self.messages() {
+            dot += &format!("    e{from} -> e{to} [style=dashed, constraint=false];\n");
+        }
+        dot += "}\n";
+        dot
+    }
+
+    /// Infers the messages between the events, as pairs of indices of the sending and receiving
+    /// events.
+    fn messages(&self) -> Vec<(usize, usize)> {
+        let mut messages = Vec::new();
+        for (process, indices) in &self.processes {
+            let mut previous = VectorClock::new(process.clone());
+            for &n in indices {
+                let clock = &self.events[n].clock;
+                // The latest event of each other process this one newly knows of.
+                let learned: Vec<usize> = self
+                    .processes
+                    .iter()
+                    .filter(|(k, _)| k != process && clock.get(k) > previous.get(k))
+                    .filter_map(|(k, events)| {
+                        let known = events
+                            .partition_point(|&m| self.events[m].clock.get(k) <= clock.get(k));
+                        known.checked_sub(1).map(|known| events[known])
+                    })
+                    .collect();
+                // Events that happen before another newly known one were learned of transitively.
+                for &m in &learned {
+                    let transitive = learned.iter().any(|&other| {
+                        self.events[m]
+                            .clock
+                            .happens_before(&self.events[other].clock)
+                    });
+                    if !transitive {
+                        messages.push((m, n));
+                    }
+                }
+                previous = clock.clone();
+            }
+        }
+        messages.sort_unstable();
+        messages
+    }
+}
+
+/// Quotes a string for JSON.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            c if c.is_control() => quoted += &format!("\\u{:04x}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes a string for Graphviz.
+fn dot_string(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// p sends to q, which forwards to r, while r does something on its own.
+    fn execution() -> Execution<&'static str> {
+        let [mut p, mut q, mut r] = ["p", "q", "r"].map(VectorClock::new);
+        let mut records = vec![("p", "send", p.send())];
+        q.receive(&records[0].2);
+        records.push(("q", "receive", q.clone()));
+        records.push(("q", "forward", q.send()));
+        records.push(("r", "local \"work\"", r.send()));
+        r.receive(&records[2].2);
+        records.push(("r", "receive", r.clone()));
+        Execution::new(records).unwrap()
+    }
+
+    #[test]
+    fn test_duplicate_event() {
+        let p = VectorClock::<&str>::new("p").send();
+        let records = [("p", "send", p.clone()), ("p", "again", p)];
+        assert_eq!(Execution::new(records).unwrap_err(), DuplicateEvent("p"));
+    }
+
+    #[test]
+    fn test_shiviz() {
+        let log = execution().to_shiviz();
+        let lines: Vec<_> = log.lines().collect();
+        assert_eq!(lines[0], SHIVIZ_REGEX);
+        assert_eq!(lines[1], "");
+        assert_eq!(lines[2..4], [r#"p {"p": 1}"#, "send"]);
+        assert_eq!(lines[10..], [r#"r {"p": 1, "q": 2, "r": 2}"#, "receive"]);
+        assert_eq!(json_string("a\"\n"), r#""a\"\u000a""#);
+    }
+
+    #[test]
+    fn test_dot() {
+        let execution = execution();
+        // q's forward is the only message r receives, though r learns of p's send too.
+        assert_eq!(execution.messages(), [(0, 1), (2, 4)]);
+        let dot = execution.to_dot();
+        assert!(dot.starts_with("digraph {\n"));
+        assert!(dot.contains("    subgraph cluster_2 {\n        label=\"r\";\n"));
+        assert!(dot.contains("        e3 [label=\"local \\\"work\\\"\"];\n"));
+        assert!(dot.contains("        e3 -> e4;\n"));
+        assert!(dot.contains("    e2 -> e4 [style=dashed, constraint=false];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
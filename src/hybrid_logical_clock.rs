@@ -15,28 +15,493 @@
 //! + performance results, and discussion.
 
 use crate::LamportClock;
-use rsntp::SntpClient;
+use rsntp::{LeapIndicator, SntpClient};
+use std::cmp::Ordering;
+use std::marker::PhantomData;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-const MASK_48_MSB: u64 = 0xFFFFFFFFFFFF0000;
 const NTP_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+/// The default bound on how far apart the clocks of any two nodes may be.
+const DEFAULT_MAX_OFFSET: Duration = Duration::from_millis(500);
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// The granularity of the physical component (`l`) of a hybrid logical clock.
+///
+/// Every resolution fixes both the length of one tick of `l` and the layout used to pack an
+/// `(l, c)` pair into a single integer. Packed values of the same resolution compare exactly like
+/// the timestamps they encode, so they can be sorted and compared without unpacking them.
+pub trait Resolution: Copy + Ord + std::hash::Hash + std::fmt::Debug + Default {
+    /// The number of ticks of `l` per second.
+    const TICKS_PER_SEC: u64;
+    /// The largest value the logical counter `c` may hold before the packed layout runs out of
+    /// room for it.
+    const MAX_COUNTER: u16;
+    /// The integer type timestamps of this resolution are packed into.
+    type Packed: Copy + Ord + std::hash::Hash + std::fmt::Debug;
+
+    /// Packs the timestamp's `l` and `c` components into a single integer.
+    ///
+    /// Panics if `l` or `c` don't fit the layout, rather than letting them spill over and break
+    /// the order of packed values.
+    fn pack(l: u64, c: u16) -> Self::Packed;
+
+    /// Inverse of [`Resolution::pack`].
+    fn unpack(packed: Self::Packed) -> (u64, u16);
+}
+
+/// Millisecond resolution, matching MongoDB-style cluster times.
+///
+/// Packed layout (`u64`): `[ l: 48 bits of milliseconds since the epoch | c: 16 bits ]`, which
+/// lasts until the year 10889.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Millis;
+
+/// Microsecond resolution, the default.
+///
+/// Packed layout (`u64`): `[ l: 52 bits of microseconds since the epoch | c: 12 bits ]`, which
+/// lasts until the year 2112. The counter is narrower than at the other resolutions, but 4096
+/// causally-related events within a single microsecond is already plenty.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Micros;
+
+/// Nanosecond resolution, matching CockroachDB's wall times.
+///
+/// 64 bits of nanoseconds since the epoch leave no room for a counter, so the packed layout is
+/// wider (`u128`): `[ l: 64 bits of nanoseconds since the epoch | 48 zero bits | c: 16 bits ]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Nanos;
+
+impl Resolution for Millis {
+    const TICKS_PER_SEC: u64 = 1_000;
+    const MAX_COUNTER: u16 = u16::MAX;
+    type Packed = u64;
+
+    fn pack(l: u64, c: u16) -> u64 {
+        assert!(l < 1 << 48, "physical time does not fit in 48 bits");
+        (l << 16) | c as u64
+    }
+
+    fn unpack(packed: u64) -> (u64, u16) {
+        (packed >> 16, packed as u16)
+    }
+}
+
+impl Resolution for Micros {
+    const TICKS_PER_SEC: u64 = 1_000_000;
+    const MAX_COUNTER: u16 = (1 << 12) - 1;
+    type Packed = u64;
+
+    fn pack(l: u64, c: u16) -> u64 {
+        assert!(l < 1 << 52, "physical time does not fit in 52 bits");
+        assert!(c <= Self::MAX_COUNTER, "counter does not fit in 12 bits");
+        (l << 12) | c as u64
+    }
+
+    fn unpack(packed: u64) -> (u64, u16) {
+        (packed >> 12, (packed & Self::MAX_COUNTER as u64) as u16)
+    }
+}
+
+impl Resolution for Nanos {
+    const TICKS_PER_SEC: u64 = 1_000_000_000;
+    const MAX_COUNTER: u16 = u16::MAX;
+    type Packed = u128;
+
+    fn pack(l: u64, c: u16) -> u128 {
+        ((l as u128) << 64) | c as u128
+    }
+
+    fn unpack(packed: u128) -> (u64, u16) {
+        ((packed >> 64) as u64, packed as u16)
+    }
+}
+
+/// The `(l, c)` pair of a hybrid logical clock, detached from any clock and its NTP state.
+///
+/// Only `l` and `c` are serialized, so the resolution must be agreed upon out of band.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(bound = ""))]
+pub struct HlcTimestamp<R: Resolution = Micros> {
+    /// Physical component, in ticks of `R` since the Unix epoch. Declared before `c` so that the
+    /// derived ordering is lexicographic over `(l, c)`.
+    l: u64,
+    /// Logical component, at most `R::MAX_COUNTER`.
+    c: u16,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    resolution: PhantomData<R>,
+}
+
+impl<R: Resolution> HlcTimestamp<R> {
+    /// Panics if `c` exceeds [`Resolution::MAX_COUNTER`], as such a timestamp couldn't be packed.
+    pub fn new(l: u64, c: u16) -> Self {
+        assert!(
+            c <= R::MAX_COUNTER,
+            "counter {c} exceeds the resolution's maximum of {}",
+            R::MAX_COUNTER
+        );
+        Self {
+            l,
+            c,
+            resolution: PhantomData,
+        }
+    }
+
+    /// The physical component, in ticks of `R` since the Unix epoch.
+    pub fn l(&self) -> u64 {
+        self.l
+    }
+
+    /// The logical component.
+    pub fn c(&self) -> u16 {
+        self.c
+    }
+
+    /// Packs the timestamp according to the layout documented on `R`.
+    pub fn pack(&self) -> R::Packed {
+        R::pack(self.l, self.c)
+    }
+
+    pub fn unpack(packed: R::Packed) -> Self {
+        let (l, c) = R::unpack(packed);
+        Self::new(l, c)
+    }
+
+    /// Converts this timestamp to another resolution without ever reordering timestamps, i.e.
+    /// `a <= b => a.convert() <= b.convert()`.
+    ///
+    /// - Refining (e.g. ms to ns) is exact and strictly order-preserving: `l` is scaled up, and a
+    ///   counter too wide for the finer layout carries into the (otherwise unused) extra ticks.
+    /// - Coarsening (e.g. ns to ms) truncates `l`. A timestamp that falls exactly on a coarse tick
+    ///   keeps its counter, while anything in between two coarse ticks gets the maximum counter,
+    ///   so that it still sorts after everything that happened at the tick itself. Distinct
+    ///   timestamps may collapse into one, but never swap places.
+    pub fn convert<S: Resolution>(&self) -> HlcTimestamp<S> {
+        if S::TICKS_PER_SEC >= R::TICKS_PER_SEC {
+            let scale = S::TICKS_PER_SEC / R::TICKS_PER_SEC;
+            let counter_span = S::MAX_COUNTER as u64 + 1;
+            let l = self
+                .l
+                .checked_mul(scale)
+                .and_then(|l| l.checked_add(self.c as u64 / counter_span))
+                .expect("timestamp is out of range for the finer resolution");
+            HlcTimestamp::new(l, (self.c as u64 % counter_span) as u16)
+        } else {
+            let scale = R::TICKS_PER_SEC / S::TICKS_PER_SEC;
+            let c = if self.l.is_multiple_of(scale) {
+                self.c.min(S::MAX_COUNTER)
+            } else {
+                S::MAX_COUNTER
+            };
+            HlcTimestamp::new(self.l / scale, c)
+        }
+    }
+
+    /// Returns the smallest timestamp greater than this one: the next counter value, or the next
+    /// tick once the counter is exhausted.
+    pub fn successor(&self) -> Self {
+        if self.c >= R::MAX_COUNTER {
+            Self::new(self.l + 1, 0)
+        } else {
+            Self::new(self.l, self.c + 1)
+        }
+    }
+
+    /// Returns the number of ticks of `R` in the given duration, rounded up.
+    pub fn ticks(duration: Duration) -> u64 {
+        duration
+            .as_nanos()
+            .div_ceil((NANOS_PER_SEC / R::TICKS_PER_SEC) as u128) as u64
+    }
+
+    /// The physical component as time since the Unix epoch. Whole seconds and the ticks within the
+    /// last one are converted separately, so that this can't overflow for any `l`.
+    pub fn as_duration(&self) -> Duration {
+        let nanos = (self.l % R::TICKS_PER_SEC) * (NANOS_PER_SEC / R::TICKS_PER_SEC);
+        Duration::new(self.l / R::TICKS_PER_SEC, nanos as u32)
+    }
+}
+
+/// A leap second, as announced by a reference clock. Reference clocks may announce it for days or
+/// weeks ahead, but it only ever happens at the end of the last day of June or December (UTC).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Leap {
+    /// No leap second is pending.
+    None,
+    /// The last minute of the day has 61 seconds. Like the kernel, the Unix clock repeats the
+    /// last second of the day.
+    Insert,
+    /// The last minute of the day has 59 seconds, so the Unix clock skips its last second.
+    Delete,
+}
+
+/// A reading of a reference clock (e.g. an NTP server).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReferenceTime {
+    /// The reference's time since the Unix epoch.
+    pub unix_time: Duration,
+    /// The reference's leap indicator.
+    pub leap: Leap,
+}
+
+/// Where a hybrid logical clock gets its physical time from.
+///
+/// The local system clock is read for every event, while the (expensive) reference clock is only
+/// consulted about once a minute to compute the system clock's offset from it.
+pub trait TimeSource {
+    /// Returns the local system time since the Unix epoch.
+    fn system_time(&mut self) -> Duration;
+
+    /// Queries the reference clock, returning `None` if it is unavailable or unsynchronized, in
+    /// which case the previously computed offset keeps being used.
+    fn reference_time(&mut self) -> Option<ReferenceTime>;
+}
+
+/// The default time source: the system clock, disciplined by an SNTP server.
+pub struct Ntp {
+    client: SntpClient,
+    server: String,
+}
+
+impl Ntp {
+    pub fn new(server: impl Into<String>) -> Self {
+        Self {
+            client: SntpClient::new(),
+            server: server.into(),
+        }
+    }
+}
+
+impl Default for Ntp {
+    fn default() -> Self {
+        Self::new("pool.ntp.org")
+    }
+}
+
+impl TimeSource for Ntp {
+    fn system_time(&mut self) -> Duration {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
+    }
+
+    fn reference_time(&mut self) -> Option<ReferenceTime> {
+        let result = self.client.synchronize(self.server.as_str()).ok()?;
+        let leap = match result.leap_indicator() {
+            LeapIndicator::NoWarning => Leap::None,
+            LeapIndicator::LastMinuteHas61Seconds => Leap::Insert,
+            LeapIndicator::LastMinuteHas59Seconds => Leap::Delete,
+            // The server itself isn't synchronized, so its time is worthless.
+            LeapIndicator::AlarmCondition => return None,
+        };
+        Some(ReferenceTime {
+            unix_time: result.datetime().unix_timestamp().ok()?,
+            leap,
+        })
+    }
+}
+
+/// The bare system clock, for when no reference clock is available (or wanted, e.g. in tests).
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn system_time(&mut self) -> Duration {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
+    }
+
+    fn reference_time(&mut self) -> Option<ReferenceTime> {
+        None
+    }
+}
+
+/// How the physical component of the clock behaves around a leap second.
+///
+/// Whatever the policy, the HLC stays monotonic (its `l` never decreases, and the counter picks
+/// up the slack) and its physical component never strays more than a second from UTC.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LeapPolicy {
+    /// Follow the Unix clock through the leap. An inserted second makes the physical time jump
+    /// back by a second, during which `l` holds still and the counter advances.
+    #[default]
+    Step,
+    /// Spread the leap linearly over the 24 hours centered on it (noon to noon), so that physical
+    /// time neither jumps nor repeats, at the cost of running slightly slow (or fast) all day.
+    Smear,
+    /// Freeze the physical component at the start of the last second of the day for as long as
+    /// the Unix clock is ambiguous (both passes through the repeated second), so that only the
+    /// counter advances.
+    Freeze,
+}
+
+/// A leap second that a reference clock has announced.
+struct PendingLeap {
+    /// The end of the day the leap happens at, in nanoseconds since the Unix epoch.
+    at: i128,
+    leap: Leap,
+    /// Whether the leap has taken place, i.e. whether the Unix clock has repeated (or skipped)
+    /// its second already.
+    passed: bool,
+    last_reading: i128,
+}
+
+impl PendingLeap {
+    const SECOND: i128 = NANOS_PER_SEC as i128;
+    const HALF_DAY: i128 = 43_200 * Self::SECOND;
+
+    /// The length of a day, in seconds, save for the leap second.
+    const DAY_SECS: i128 = 86_400;
+
+    /// Whether the given day (since the Unix epoch) is one leap seconds are scheduled for at its
+    /// end, i.e. the last day of June or December.
+    fn is_leap_day(day: u64) -> bool {
+        // The month and day of the month of the next day, as in `civil_from_days` from Howard
+        // Hinnant's "chrono-Compatible Low-Level Date Algorithms".
+        let z = day + 1 + 719_468;
+        let doe = z % 146_097;
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let (month, day_of_month) = ((mp + 2) % 12 + 1, doy - (153 * mp + 2) / 5 + 1);
+        day_of_month == 1 && (month == 1 || month == 7)
+    }
+
+    /// Adjusts a reading of the (Unix) physical time according to the policy.
+    fn adjust(&mut self, reading: i128, policy: LeapPolicy) -> i128 {
+        // An inserted second makes the Unix clock run backwards within the last second of the
+        // day; otherwise, the leap has certainly passed once the clock reaches the next day.
+        if reading >= self.at
+            || (self.leap == Leap::Insert
+                && reading >= self.at - Self::SECOND
+                && reading < self.last_reading)
+        {
+            self.passed = true;
+        }
+        self.last_reading = reading;
+
+        match policy {
+            LeapPolicy::Step => reading,
+            LeapPolicy::Freeze => {
+                if self.leap == Leap::Insert && (self.at - Self::SECOND..self.at).contains(&reading)
+                {
+                    self.at - Self::SECOND
+                } else {
+                    reading
+                }
+            }
+            LeapPolicy::Smear => {
+                let start = self.at - Self::HALF_DAY;
+                if reading < start {
+                    return reading;
+                }
+                let delta = if self.leap == Leap::Insert { 1 } else { -1 };
+                // The real (SI) time elapsed since the smear began, which runs a second ahead of
+                // (or behind) the Unix clock once the leap has passed.
+                let elapsed = reading - start + if self.passed { delta * Self::SECOND } else { 0 };
+                start + elapsed * Self::DAY_SECS / (Self::DAY_SECS + delta)
+            }
+        }
+    }
+
+    /// Whether the leap, and any smearing of it, is over.
+    fn is_over(&self, reading: i128) -> bool {
+        self.passed && reading >= self.at + Self::HALF_DAY
+    }
+}
+
+impl<R: Resolution> std::fmt::Debug for HlcTimestamp<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HlcTimestamp")
+            .field("l", &self.l)
+            .field("c", &self.c)
+            .finish()
+    }
+}
+
+/// Formats the timestamp as `l:c`.
+impl<R: Resolution> std::fmt::Display for HlcTimestamp<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.l, self.c)
+    }
+}
 
 struct ClockSync {
-    ntp: SntpClient,
-    time_offset: f64,
-    last_ntp_sync: SystemTime,
+    source: Box<dyn TimeSource + Send>,
+    /// The offset of the reference clock from the system clock, in nanoseconds.
+    time_offset: i128,
+    /// The system time of the last synchronization with the reference clock, if any.
+    last_ntp_sync: Option<Duration>,
+    leap_policy: LeapPolicy,
+    max_offset: Duration,
+    pending_leap: Option<PendingLeap>,
+}
+
+impl ClockSync {
+    fn new(source: Box<dyn TimeSource + Send>) -> Self {
+        Self {
+            source,
+            time_offset: 0,
+            last_ntp_sync: None,
+            leap_policy: LeapPolicy::default(),
+            max_offset: DEFAULT_MAX_OFFSET,
+            pending_leap: None,
+        }
+    }
+
+    /// Reads the physical time, in nanoseconds since the Unix epoch.
+    fn now(&mut self) -> i128 {
+        let system_now = self.source.system_time();
+
+        // If we're out of the sync-free window, we need to resynchronize.
+        if self
+            .last_ntp_sync
+            .is_none_or(|last| system_now.saturating_sub(last) >= NTP_SYNC_INTERVAL)
+        {
+            if let Some(reference) = self.source.reference_time() {
+                self.time_offset = reference.unix_time.as_nanos() as i128
+                    - self.source.system_time().as_nanos() as i128;
+                let day = reference.unix_time.as_secs() / 86_400;
+                if reference.leap != Leap::None && PendingLeap::is_leap_day(day) {
+                    let at = (day + 1) as i128 * 86_400 * PendingLeap::SECOND;
+                    if self
+                        .pending_leap
+                        .as_ref()
+                        .is_none_or(|pending| pending.at != at)
+                    {
+                        self.pending_leap = Some(PendingLeap {
+                            at,
+                            leap: reference.leap,
+                            passed: false,
+                            last_reading: 0,
+                        });
+                    }
+                }
+            }
+            // Even if the reference is unavailable, don't hammer it on every single event.
+            self.last_ntp_sync = Some(system_now);
+        }
+
+        // Now recompute current time using the freshest system clock + offset.
+        let reading = self.source.system_time().as_nanos() as i128 + self.time_offset;
+        match self.pending_leap.as_mut() {
+            Some(pending) if pending.is_over(reading) => {
+                self.pending_leap = None;
+                reading
+            }
+            Some(pending) => pending.adjust(reading, self.leap_policy),
+            None => reading,
+        }
+    }
 }
 
-pub struct HybridLogicalClock {
+pub struct HybridLogicalClock<R: Resolution = Micros> {
     /// The maximum physical timestamp (PT) observed so far, either from local events or received
     /// messages. This tracks the highest PT known to the node and is monotonically non-decreasing.
-    l: f64,
+    ///
+    /// Measured in ticks of the clock's [`Resolution`] since the Unix epoch.
+    l: u64,
     /// The logical counter used to distinguish causally related events that happen at the same
     /// physical time `l`. This counter increments when multiple events occur with the same `l`.
     ///
-    /// We choose to represent this as a 16-bit integer for compaction, as rounding the physical
-    /// timestamp to the 48 most significant bits allows for microsecond-level granularity and
-    /// 16 bits for `c` gives it room to grow up to 65536, which is more than enough (probably).
+    /// We choose to represent this as a 16-bit integer for compaction; how many of those bits are
+    /// usable depends on the packed layout of the resolution (see [`Resolution::MAX_COUNTER`]).
+    /// Should the counter ever run out, `l` is advanced by a single tick instead.
     c: u16,
     /// A bundle of all NTP-related state.
     ///
@@ -44,73 +509,117 @@ pub struct HybridLogicalClock {
     /// (`self.l` and `self.c`) so we can disregard all the NTP-related stuff. Wrapping it as an
     /// `Option` allows it to have essentially no memory footprint unless we'll use it.
     sync: Option<ClockSync>,
+    resolution: PhantomData<R>,
 }
 
 impl HybridLogicalClock {
     /// A boring old constructor.
     pub fn new() -> Self {
+        Self::with_resolution()
+    }
+}
+
+impl Default for HybridLogicalClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: Resolution> HybridLogicalClock<R> {
+    /// Constructs a clock whose physical component has resolution `R`, e.g.
+    /// `HybridLogicalClock::<Millis>::with_resolution()`.
+    pub fn with_resolution() -> Self {
         Self {
-            l: 0.0,
+            l: 0,
             c: 0,
-            sync: Some(ClockSync {
-                ntp: SntpClient::new(),
-                time_offset: 0.0,
-                last_ntp_sync: SystemTime::UNIX_EPOCH,
-            }),
+            sync: Some(ClockSync::new(Box::new(Ntp::default()))),
+            resolution: PhantomData,
         }
     }
 
-    /// Compacts the `l` and `c` timestamps of the clock into a single 64-bit value.
-    fn compact_timestamps(&self) -> u64 {
-        let rounded_l = (self.l.to_bits()) & MASK_48_MSB;
-        rounded_l + self.c as u64
+    /// Replaces the clock's source of physical time, which is NTP-disciplined system time by
+    /// default.
+    pub fn with_time_source(mut self, source: impl TimeSource + Send + 'static) -> Self {
+        let mut sync = ClockSync::new(Box::new(source));
+        if let Some(previous) = self.sync.take() {
+            sync.leap_policy = previous.leap_policy;
+            sync.max_offset = previous.max_offset;
+        }
+        self.sync = Some(sync);
+        self
     }
 
-    /// Unpacks a 64 bit representation of the HLC into the struct representation.
-    fn decompose_into_timestamps(value: u64) -> Self {
-        let l = f64::from_bits(value & MASK_48_MSB);
-        let c = (value & !MASK_48_MSB) as u16;
-        HybridLogicalClock { l, c, sync: None }
+    /// Sets the maximum offset between the clocks of any two nodes in the system, which is
+    /// 500ms by default. Nothing enforces the bound, but anything relying on it for correctness
+    /// (see [`crate::transaction`]) breaks if it is exceeded.
+    pub fn with_max_offset(mut self, max_offset: Duration) -> Self {
+        if let Some(sync) = self.sync.as_mut() {
+            sync.max_offset = max_offset;
+        }
+        self
     }
 
-    /// Gets the current hybrid timestamp as duration of seconds represented by a 64-bit float.
-    fn get_current_timestamp(&mut self) -> f64 {
-        #[inline]
-        fn system_time_now_as_secs() -> f64 {
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs_f64()
+    /// Returns the configured maximum clock offset (see [`HybridLogicalClock::with_max_offset`]).
+    pub fn max_offset(&self) -> Duration {
+        self.sync
+            .as_ref()
+            .map_or(DEFAULT_MAX_OFFSET, |sync| sync.max_offset)
+    }
+
+    /// Reads the current physical time, in ticks since the Unix epoch, without registering an
+    /// event. Note that this may well be behind `l`.
+    ///
+    /// Panics if the clock has no time source, i.e. if it was received in a message.
+    pub fn physical_now(&mut self) -> u64 {
+        self.get_current_timestamp()
+    }
+
+    /// Sets how the clock handles leap seconds announced by its time source.
+    pub fn with_leap_policy(mut self, policy: LeapPolicy) -> Self {
+        if let Some(sync) = self.sync.as_mut() {
+            sync.leap_policy = policy;
         }
+        self
+    }
 
-        let sync = self.sync.as_mut().unwrap();
+    /// Returns the clock's current `(l, c)` pair.
+    pub fn timestamp(&self) -> HlcTimestamp<R> {
+        HlcTimestamp::new(self.l, self.c)
+    }
 
-        // If we're out of the sync-free window, we need to resynchronize.
-        if SystemTime::now()
-            .duration_since(sync.last_ntp_sync)
-            .unwrap_or(Duration::ZERO)
-            >= NTP_SYNC_INTERVAL
-        {
-            let ntp_now = sync
-                .ntp
-                .synchronize("pool.ntp.org")
-                .unwrap()
-                .datetime()
-                .unix_timestamp()
-                .unwrap()
-                .as_secs_f64();
-            let system_now = system_time_now_as_secs();
+    /// Converts this clock's timestamps to another resolution (see [`HlcTimestamp::convert`]).
+    /// The returned clock carries no NTP state, just like one produced by `send`.
+    pub fn convert<S: Resolution>(&self) -> HybridLogicalClock<S> {
+        HybridLogicalClock::from(self.timestamp().convert::<S>())
+    }
 
-            sync.time_offset = ntp_now - system_now;
-            sync.last_ntp_sync = SystemTime::now();
-        }
+    /// Compacts the `l` and `c` timestamps of the clock into a single value, laid out as
+    /// documented on the clock's resolution.
+    pub fn compact_timestamps(&self) -> R::Packed {
+        self.timestamp().pack()
+    }
 
-        // Now recompute current time using the freshest system clock + offset.
-        system_time_now_as_secs() + sync.time_offset
+    /// Unpacks a packed representation of the HLC into the struct representation.
+    pub fn decompose_into_timestamps(value: R::Packed) -> Self {
+        Self::from(HlcTimestamp::unpack(value))
+    }
+
+    /// Advances the counter past `c`, or, if the counter is exhausted, advances `l` by a single
+    /// tick instead. Borrowing a tick from the future keeps the clock strictly increasing, at the
+    /// cost of drifting (very slightly) ahead of physical time.
+    fn advance_counter(&mut self, c: u16) {
+        let next = HlcTimestamp::<R>::new(self.l, c).successor();
+        (self.l, self.c) = (next.l, next.c);
+    }
+
+    /// Gets the current hybrid timestamp as a number of ticks since the Unix epoch.
+    fn get_current_timestamp(&mut self) -> u64 {
+        let nanos = self.sync.as_mut().unwrap().now();
+        (nanos.max(0) / (NANOS_PER_SEC / R::TICKS_PER_SEC) as i128) as u64
     }
 }
 
-impl LamportClock for HybridLogicalClock {
+impl<R: Resolution> LamportClock for HybridLogicalClock<R> {
     fn bump(&mut self) {
         let pt = self.get_current_timestamp();
         if pt > self.l {
@@ -121,7 +630,7 @@ impl LamportClock for HybridLogicalClock {
         } else {
             // Otherwise, this is yet another event at the current `l` value, and we should update
             // our event counter accordingly.
-            self.c += 1;
+            self.advance_counter(self.c);
         }
     }
 
@@ -129,11 +638,7 @@ impl LamportClock for HybridLogicalClock {
         self.bump();
 
         // The receiving clock doesn't care about the ntp client, just the timestamps.
-        Self {
-            c: self.c,
-            l: self.l,
-            sync: None,
-        }
+        Self::from(self.timestamp())
     }
 
     fn receive(&mut self, incoming_clock: &Self) {
@@ -141,52 +646,349 @@ impl LamportClock for HybridLogicalClock {
         let pt = self.get_current_timestamp();
 
         self.l = pt.max(prev_l.max(incoming_clock.l));
-        self.c = match (self.l == prev_l, self.l == incoming_clock.l) {
+        match (self.l == prev_l, self.l == incoming_clock.l) {
             // The incoming clock and us both are at the same `l` value, so we need to ensure the
             // counter of events occurring at timestamp `l` is greater than both what our clock and
             // the incoming clock had.
-            (true, true) => 1 + u16::max(self.c, incoming_clock.c),
+            (true, true) => self.advance_counter(u16::max(self.c, incoming_clock.c)),
             // Our clock's max timestamp is ahead of the incoming one, so we just need to ensure
             // our new `c` value is greater than what the previous version of this clock had.
-            (true, false) => 1 + self.c,
+            (true, false) => self.advance_counter(self.c),
             // The incoming clock's max timestamp is ahead of ours, so we need to ensure that
             // our new `c` value is greater than what they had.
-            (false, true) => 1 + incoming_clock.c,
+            (false, true) => self.advance_counter(incoming_clock.c),
             // We're at a new max timestamp, so we're the first event and can reset the counter!
-            (false, false) => 0,
+            (false, false) => self.c = 0,
+        }
+    }
+}
+
+impl<R: Resolution> From<HlcTimestamp<R>> for HybridLogicalClock<R> {
+    fn from(timestamp: HlcTimestamp<R>) -> Self {
+        Self {
+            l: timestamp.l,
+            c: timestamp.c,
+            sync: None,
+            resolution: PhantomData,
+        }
+    }
+}
+
+macro_rules! impl_packed_conversions {
+    ($($resolution:ty => $packed:ty),*) => {$(
+        impl From<$packed> for HybridLogicalClock<$resolution> {
+            fn from(value: $packed) -> Self {
+                Self::decompose_into_timestamps(value)
+            }
+        }
+
+        impl From<HybridLogicalClock<$resolution>> for $packed {
+            fn from(value: HybridLogicalClock<$resolution>) -> $packed {
+                value.compact_timestamps()
+            }
+        }
+    )*};
+}
+
+impl_packed_conversions!(Millis => u64, Micros => u64, Nanos => u128);
+
+impl<R: Resolution> From<HybridLogicalClock<R>> for Duration {
+    fn from(value: HybridLogicalClock<R>) -> Duration {
+        value.timestamp().as_duration()
+    }
+}
+
+/// Deserializes an `(l, c)` pair, rejecting counters too wide for the packed layout of `R`.
+#[cfg(feature = "serde")]
+impl<'de, R: Resolution> serde::Deserialize<'de> for HlcTimestamp<R> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "HlcTimestamp")]
+        struct Raw {
+            l: u64,
+            c: u16,
         }
+        let Raw { l, c } = Raw::deserialize(deserializer)?;
+        if c > R::MAX_COUNTER {
+            return Err(D::Error::custom(format!(
+                "counter {c} exceeds the resolution's maximum of {}",
+                R::MAX_COUNTER
+            )));
+        }
+        Ok(Self::new(l, c))
     }
 }
 
-impl From<u64> for HybridLogicalClock {
-    fn from(value: u64) -> Self {
-        Self::decompose_into_timestamps(value)
+/// Serializes the clock's timestamps only, as an [`HlcTimestamp`].
+#[cfg(feature = "serde")]
+impl<R: Resolution> serde::Serialize for HybridLogicalClock<R> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.timestamp().serialize(serializer)
     }
 }
 
-impl From<HybridLogicalClock> for u64 {
-    fn from(value: HybridLogicalClock) -> u64 {
-        value.compact_timestamps()
+/// Deserializes a clock from its timestamps, giving it a fresh (default) sync state.
+#[cfg(feature = "serde")]
+impl<'de, R: Resolution> serde::Deserialize<'de> for HybridLogicalClock<R> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let timestamp = HlcTimestamp::<R>::deserialize(deserializer)?;
+        let mut clock = Self::with_resolution();
+        (clock.l, clock.c) = (timestamp.l, timestamp.c);
+        Ok(clock)
     }
 }
 
-impl Into<Duration> for HybridLogicalClock {
-    fn into(self) -> Duration {
-        Duration::from_secs_f64(self.l)
+impl<R: Resolution> std::fmt::Debug for HybridLogicalClock<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HybridLogicalClock")
+            .field("l", &self.l)
+            .field("c", &self.c)
+            .finish()
     }
 }
 
-impl PartialEq<Self> for HybridLogicalClock {
+impl<R: Resolution> PartialEq<Self> for HybridLogicalClock<R> {
     fn eq(&self, other: &Self) -> bool {
         self.l == other.l && self.c == other.c
     }
 }
 
-impl PartialOrd for HybridLogicalClock {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        if self.l == other.l {
-            return self.c.partial_cmp(&other.c);
+impl<R: Resolution> PartialOrd for HybridLogicalClock<R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.timestamp().cmp(&other.timestamp()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+
+    #[test]
+    fn test_resolutions() {
+        // Packing is lossless and preserves the order of timestamps at every resolution.
+        fn assert_packing<R: Resolution>() {
+            let timestamps = [
+                (0, 0),
+                (0, 1),
+                (0, R::MAX_COUNTER),
+                (1, 0),
+                (1_760_000_000, 7),
+            ]
+            .map(|(secs, c)| HlcTimestamp::<R>::new(secs * R::TICKS_PER_SEC, c));
+            for pair in timestamps.windows(2) {
+                assert!(pair[0].pack() < pair[1].pack());
+            }
+            for ts in timestamps {
+                assert_eq!(HlcTimestamp::unpack(ts.pack()), ts);
+            }
         }
-        self.l.partial_cmp(&other.l)
+        assert_packing::<Millis>();
+        assert_packing::<Micros>();
+        assert_packing::<Nanos>();
+
+        let clock = HybridLogicalClock::<Millis>::from(HlcTimestamp::new(1_760_000_000_123, 42));
+        assert_eq!(
+            HybridLogicalClock::<Millis>::from(u64::from(clock.convert::<Millis>())),
+            clock
+        );
+        assert_eq!(
+            Duration::from(clock),
+            Duration::from_millis(1_760_000_000_123)
+        );
+        // Millisecond times far too large for a u64 of nanoseconds still convert.
+        assert_eq!(
+            HlcTimestamp::<Millis>::new(1 << 47, 0).as_duration(),
+            Duration::from_millis(1 << 47)
+        );
+
+        // Refining and coarsening back again is the identity...
+        let ts = HlcTimestamp::<Millis>::new(1_760_000_000_123, u16::MAX);
+        assert_eq!(ts.convert::<Nanos>().convert::<Millis>(), ts);
+        // ...and a counter too wide for the finer layout carries into the extra ticks.
+        assert_eq!(
+            ts.convert::<Micros>(),
+            HlcTimestamp::new(1_760_000_000_123_000 + 15, 4095)
+        );
+
+        // Neither coarsening nor refining ever reorders timestamps.
+        let mut timestamps = Vec::new();
+        for l in 1_760_000_000_000_000..1_760_000_000_002_500 {
+            for c in [0, 1, 4095, 4096, u16::MAX] {
+                timestamps.push(HlcTimestamp::<Nanos>::new(l, c));
+            }
+        }
+        for pair in timestamps.windows(2) {
+            assert!(pair[0].convert::<Millis>() <= pair[1].convert::<Millis>());
+            assert!(pair[0].convert::<Micros>() <= pair[1].convert::<Micros>());
+            assert!(
+                pair[0].convert::<Micros>().convert::<Nanos>()
+                    <= pair[1].convert::<Micros>().convert::<Nanos>()
+            );
+        }
+        // A timestamp on a coarse tick keeps its counter.
+        assert_eq!(
+            HlcTimestamp::<Nanos>::new(1_760_000_000_000_000, 3).convert::<Micros>(),
+            HlcTimestamp::new(1_760_000_000_000, 3)
+        );
+    }
+
+    /// Simulated time, in which the system clock is read from a shared cell and agrees with the
+    /// reference clock, which announces a leap second until it has happened.
+    struct SimulatedTime {
+        now: Arc<AtomicU64>,
+        leap: Leap,
+        leap_at: u64,
+    }
+
+    impl TimeSource for SimulatedTime {
+        fn system_time(&mut self) -> Duration {
+            Duration::from_nanos(self.now.load(AtomicOrdering::Relaxed))
+        }
+
+        fn reference_time(&mut self) -> Option<ReferenceTime> {
+            let unix_time = self.system_time();
+            let leap = if unix_time.as_secs() < self.leap_at {
+                self.leap
+            } else {
+                Leap::None
+            };
+            Some(ReferenceTime { unix_time, leap })
+        }
+    }
+
+    #[test]
+    fn test_leap_second() {
+        // Midnight at the end of 2016-12-31, the latest leap second (an insertion) at the time of
+        // writing.
+        const LEAP_AT: u64 = 1_483_228_800;
+        const SECOND: u64 = 1_000_000_000;
+
+        fn simulate(leap: Leap, policy: LeapPolicy) {
+            // Maps the real (SI) time to what the Unix clock reads at that time, which repeats
+            // (or skips) the last second of the day.
+            let unix_time = |si: u64| match leap {
+                Leap::Insert if si >= LEAP_AT * SECOND => si - SECOND,
+                Leap::Delete if si >= (LEAP_AT - 1) * SECOND => si + SECOND,
+                _ => si,
+            };
+
+            let now = Arc::new(AtomicU64::new(0));
+            let source = SimulatedTime {
+                now: Arc::clone(&now),
+                leap,
+                leap_at: LEAP_AT,
+            };
+            let mut clock = HybridLogicalClock::<Millis>::with_resolution()
+                .with_time_source(source)
+                .with_leap_policy(policy);
+
+            // Run the clock from a few days before the leap to a day after it, ticking every few
+            // seconds, and every 10ms around the leap itself. The leap is announced all along, but
+            // only the last day of the year ends with it.
+            let mut si = (LEAP_AT - 3 * 86_400) * SECOND;
+            let mut previous = clock.timestamp();
+            while si < (LEAP_AT + 86_400) * SECOND {
+                let reading = unix_time(si);
+                now.store(reading, AtomicOrdering::Relaxed);
+                clock.bump();
+
+                let timestamp = clock.timestamp();
+                assert!(previous < timestamp, "{policy:?}: clock went backwards");
+                let drift = (timestamp.as_duration().as_nanos() as i128 - reading as i128).abs();
+                assert!(
+                    drift <= SECOND as i128,
+                    "{policy:?}: drifted {drift}ns from UTC"
+                );
+                match policy {
+                    // Smearing never makes physical time stand still...
+                    LeapPolicy::Smear => assert_eq!(timestamp.c, 0),
+                    // ...while freezing holds it still for the entirety of the ambiguous second.
+                    LeapPolicy::Freeze
+                        if leap == Leap::Insert
+                            && (LEAP_AT - 1..LEAP_AT).contains(&(reading / SECOND)) =>
+                    {
+                        assert_eq!(timestamp.l, (LEAP_AT - 1) * 1000);
+                    }
+                    _ => {}
+                }
+                if si.abs_diff(LEAP_AT * SECOND) >= 43_200 * SECOND {
+                    assert_eq!(
+                        timestamp.l,
+                        reading / 1_000_000,
+                        "{policy:?}: strayed from UTC away from the leap"
+                    );
+                }
+                previous = timestamp;
+
+                let near_leap = si.abs_diff(LEAP_AT * SECOND) < 3 * SECOND;
+                si += if near_leap { SECOND / 100 } else { 7 * SECOND };
+            }
+        }
+
+        for policy in [LeapPolicy::Step, LeapPolicy::Smear, LeapPolicy::Freeze] {
+            simulate(Leap::Insert, policy);
+            simulate(Leap::Delete, policy);
+        }
+
+        let day = LEAP_AT / 86_400 - 1;
+        assert!(PendingLeap::is_leap_day(day));
+        assert!(!PendingLeap::is_leap_day(day - 1));
+        // 2015-06-30, and 2016-02-29 (the end of a month, but not one leap seconds are scheduled
+        // for).
+        assert!(PendingLeap::is_leap_day(16_616));
+        assert!(!PendingLeap::is_leap_day(16_860));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut clock = HybridLogicalClock::<Nanos>::with_resolution()
+            .with_time_source(SystemClock)
+            .with_max_offset(Duration::from_millis(5));
+        clock.bump();
+        clock.bump();
+        let message = clock.send();
+
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(json, format!(r#"{{"l":{},"c":{}}}"#, message.l, message.c));
+        let mut decoded: HybridLogicalClock<Nanos> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, message);
+        // A deserialized clock can keep on ticking, with a fresh sync state.
+        assert!(decoded.sync.is_some());
+        assert_eq!(decoded.max_offset(), DEFAULT_MAX_OFFSET);
+        decoded.sync = Some(ClockSync::new(Box::new(SystemClock)));
+        decoded.bump();
+        assert!(decoded > message);
+
+        let config = bincode::config::standard();
+        let bytes = bincode::serde::encode_to_vec(&clock, config).unwrap();
+        let (binary, _): (HybridLogicalClock<Nanos>, _) =
+            bincode::serde::decode_from_slice(&bytes, config).unwrap();
+        assert_eq!(binary, clock);
+
+        // Counters too wide for the resolution's packed layout are rejected.
+        let wide = r#"{"l":1,"c":5000}"#;
+        assert!(serde_json::from_str::<HybridLogicalClock<Micros>>(wide).is_err());
+        let decoded: HybridLogicalClock<Millis> = serde_json::from_str(wide).unwrap();
+        assert_eq!(decoded.timestamp(), HlcTimestamp::new(1, 5000));
+    }
+
+    #[test]
+    fn test_counter_exhaustion() {
+        let mut clock = HybridLogicalClock::<Micros>::from(HlcTimestamp::new(10, 4094));
+        clock.advance_counter(clock.c);
+        assert_eq!(clock.timestamp(), HlcTimestamp::new(10, 4095));
+        clock.advance_counter(clock.c);
+        assert_eq!(clock.timestamp(), HlcTimestamp::new(11, 0));
+
+        // A counter the layout has no room for is rejected instead of spilling into `l`.
+        assert!(std::panic::catch_unwind(|| HlcTimestamp::<Micros>::new(5, 5000)).is_err());
+        assert!(std::panic::catch_unwind(|| Micros::pack(5, 5000)).is_err());
+        // Nor does a physical time too large for the layout get truncated.
+        assert!(std::panic::catch_unwind(|| Millis::pack(1 << 48, 0)).is_err());
     }
 }
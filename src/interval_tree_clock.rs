@@ -20,6 +20,7 @@ macro_rules! rc {
     };
 }
 
+#[derive(Clone)]
 #[repr(transparent)]
 pub struct IntervalTreeClock {
     /// The underlying representation of an `IntervalTreeClock` is a `Stamp`, which encodes the
@@ -54,6 +55,24 @@ impl IntervalTreeClock {
         (Self::from(stamp1), Self::from(stamp2))
     }
 
+    /// Returns an anonymous clock (one that can't register events) whose event is the least upper
+    /// bound of both clocks' events, i.e. everything either clock knows of.
+    pub fn join_events(&self, other: &Self) -> Self {
+        Self::from(Stamp::new(
+            Id::Empty,
+            self.stamp.event.join(&other.stamp.event),
+        ))
+    }
+
+    /// Returns an anonymous clock (one that can't register events) whose event is the greatest
+    /// lower bound of both clocks' events, i.e. what both clocks know of.
+    pub fn meet_events(&self, other: &Self) -> Self {
+        Self::from(Stamp::new(
+            Id::Empty,
+            self.stamp.event.meet(&other.stamp.event),
+        ))
+    }
+
     fn bump(&mut self) {
         self.stamp = self.stamp.event();
     }
@@ -68,6 +87,12 @@ impl IntervalTreeClock {
     }
 }
 
+impl Default for IntervalTreeClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LamportClock for IntervalTreeClock {
     fn bump(&mut self) {
         IntervalTreeClock::bump(self)
@@ -105,8 +130,33 @@ impl PartialEq<Self> for IntervalTreeClock {
     }
 }
 
+/// Serializes the clock's stamp, i.e. its id and event trees.
+#[cfg(feature = "serde")]
+impl serde::Serialize for IntervalTreeClock {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.stamp.serialize(serializer)
+    }
+}
+
+/// Deserializes a clock from its stamp, rejecting trees that aren't in normal form (which every
+/// operation on stamps assumes).
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for IntervalTreeClock {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stamp = Stamp::deserialize(deserializer)?;
+        if !stamp.id.is_normal() || !stamp.event.is_normal() {
+            return Err(serde::de::Error::custom(
+                "interval tree clock stamp is not in normal form",
+            ));
+        }
+        Ok(Self::from(stamp))
+    }
+}
+
 /// A logical clock representation upon which a set of core operations (fork, event, join) models
 /// a causality tracking mechanism.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Stamp {
     id: Id,
     event: Event,
@@ -234,8 +284,10 @@ impl Stamp {
         assert_ne!(self.id, Id::Empty);
 
         let new_event = {
+            // Filling is preferable, as it doesn't grow the tree, but only counts as an event if it
+            // actually inflated something.
             let filled_event = fill(self);
-            if filled_event == self.event {
+            if filled_event != self.event {
                 filled_event
             } else {
                 let (grown_event, _cost) = grow(self);
@@ -261,13 +313,6 @@ impl Stamp {
         Self::new(self.id.sum(&other.id), self.event.join(&other.event))
     }
 
-    /// There can be several equivalent representations for a given function; in ITC we wish to
-    /// keep stamps in *normal form* for the representations of both id and event functions, not
-    /// only to have compact representations but also to allow simple definitions on stamps.
-    fn norm(&self) -> Self {
-        Self::new(self.id.norm(), self.event.norm())
-    }
-
     /// Comparison of ITC can be derived from the point-wise comparison, which can be computed
     /// through a recursive function over normalized event trees; i.e. (i1, e1) <= (i2, e2) if, and
     /// only if, e1 <= e2.
@@ -277,6 +322,7 @@ impl Stamp {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum Id {
     /// No ownership over the id's interval domain.
     Empty,
@@ -338,12 +384,26 @@ impl Id {
         }
     }
 
+    /// Returns whether the whole tree is in normal form, i.e. whether no subtree could be
+    /// simplified by [`Id::norm`].
+    #[cfg(feature = "serde")]
+    fn is_normal(&self) -> bool {
+        match self {
+            Id::Empty | Id::Full => true,
+            Id::Split(l, r) => {
+                !matches!((&**l, &**r), (Id::Empty, Id::Empty) | (Id::Full, Id::Full))
+                    && l.is_normal()
+                    && r.is_normal()
+            }
+        }
+    }
+
     /// Normalization of the id component can be obtained by recursively applying this function
     /// when building the id tree.
     fn norm(&self) -> Self {
         use Id::{Empty, Full, Split};
 
-        if let Split(l, r) = &*self {
+        if let Split(l, r) = self {
             if let (Empty, Empty) = (&**l, &**r) {
                 return Empty;
             }
@@ -370,6 +430,7 @@ impl PartialEq for Id {
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum Event {
     /// Represents an element over its interval domain whose value is constant throughout.
     N(u32),
@@ -420,6 +481,49 @@ impl Event {
         }
     }
 
+    /// The pointwise minimum of the two functions, which mirrors [`Event::join`]:
+    /// ```text
+    /// meet(n1, n2)                     = min(n1, n2)
+    /// meet(n1, (n2, l2, r2))           = meet((n1, 0, 0), (n2, l2, r2))
+    /// meet((n1, l1, r1), n2)           = meet((n1, l1, r1), (n2, 0, 0))
+    /// meet((n1, l1, r1), (n2, l2, r2)) = meet((n2, l2, r2), (n1, l1, r1)), if n1 > n2
+    /// meet((n1, l1, r1), (n2, l2, r2)) = norm((n1, meet(l1, lift(l2, n2 - n1)),
+    ///                                              meet(r1, lift(r2, n2 - n1))))
+    /// ```
+    fn meet(&self, other: &Self) -> Self {
+        use Event::*;
+
+        match (self, other) {
+            (N(n1), N(n2)) => N(*n1.min(n2)),
+            (N(n1), Split(_, _, _)) => Event::split_from(n1).meet(other),
+            (Split(_, _, _), N(n2)) => self.meet(&Event::split_from(n2)),
+            (Split(n1, l1, r1), Split(n2, l2, r2)) => {
+                if n1 > n2 {
+                    other.meet(self)
+                } else {
+                    let n = n2 - n1;
+                    let (left, right) = (l1.meet(&l2.lift(n)), r1.meet(&r2.lift(n)));
+                    Split(*n1, rc!(left), rc!(right)).norm()
+                }
+            }
+        }
+    }
+
+    /// Returns whether the whole tree is in normal form, i.e. whether no subtree could be
+    /// simplified by [`Event::norm`].
+    #[cfg(feature = "serde")]
+    fn is_normal(&self) -> bool {
+        match self {
+            N(_) => true,
+            Event::Split(_, l, r) => {
+                !matches!((&**l, &**r), (N(m1), N(m2)) if m1 == m2)
+                    && u32::min(l.min(), r.min()) == 0
+                    && l.is_normal()
+                    && r.is_normal()
+            }
+        }
+    }
+
     fn norm(&self) -> Self {
         match self {
             N(n) => N(*n),
@@ -427,12 +531,10 @@ impl Event {
                 let (e1, e2) = (e1.as_ref(), e2.as_ref());
 
                 // norm((n,m,m)) = n + m
-                if let N(m1) = e1 {
-                    if let N(m2) = e2 {
-                        if m1 == m2 {
-                            return N(*n + m1);
-                        }
-                    }
+                if let (N(m1), N(m2)) = (e1, e2)
+                    && m1 == m2
+                {
+                    return N(*n + m1);
                 }
 
                 // norm((n, e1, e2)) = (n+m, e1.sink(m), e2.sink(m)), where m = min(min(e1), min(e2)).
@@ -474,12 +576,14 @@ impl Event {
     }
 
     /// We define leq(e1, e2) as follows:
-    /// - leq(n1, n2)                      = n1 <= n2
-    /// - leq(n1, (n2, l2, r2))            = n1 <= n2
-    /// - leq((n1, l1, r1), n2)            = n1 <= n2 AND leq(l1.lift(n1), n2)
-    ///                                               AND leq(r1.lift(n1), n2)
-    /// - leq((n1, l1, r1), (n2, l2, r2))  = n1 <= n2 AND leq(l1.lift(n1), l2.lift(n2))
-    ///                                               AND leq(r1.lift(n1), r2.lift(n2))
+    /// ```text
+    /// leq(n1, n2)                      = n1 <= n2
+    /// leq(n1, (n2, l2, r2))            = n1 <= n2
+    /// leq((n1, l1, r1), n2)            = n1 <= n2 AND leq(l1.lift(n1), n2)
+    ///                                             AND leq(r1.lift(n1), n2)
+    /// leq((n1, l1, r1), (n2, l2, r2))  = n1 <= n2 AND leq(l1.lift(n1), l2.lift(n2))
+    ///                                             AND leq(r1.lift(n1), r2.lift(n2))
+    /// ```
     fn leq(&self, other: &Self) -> bool {
         use Event::{N, Split};
 
@@ -497,3 +601,78 @@ impl Event {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bump_inflates() {
+        // Events used to only inflate the event tree when filling had nothing to simplify, so a
+        // process's first bump after a fork (where filling does nothing) was a no-op.
+        let (mut p, _) = IntervalTreeClock::new().fork();
+        let first = p.send();
+        let second = p.send();
+        assert!(first < second);
+        assert!(first < p && second == p);
+    }
+
+    #[test]
+    fn test_join_and_meet_events() {
+        let (mut p, q) = IntervalTreeClock::new().fork();
+        let (mut q, mut r) = q.fork();
+        p.bump();
+        q.receive(&p.send());
+        p.bump();
+        r.bump();
+
+        let join = p.join_events(&q).join_events(&r);
+        let meet = p.meet_events(&q).meet_events(&r);
+        for clock in [&p, &q, &r] {
+            assert!(meet <= *clock && *clock <= join);
+        }
+        // Only p's first two events are known to both p and q, and r knows of none of theirs.
+        assert!(p.meet_events(&q) < p);
+        assert!(p.meet_events(&q).partial_cmp(&q) == Some(Ordering::Less));
+        assert!(meet == IntervalTreeClock::new());
+        assert!(p.meet_events(&join) == p);
+        assert!(r.join_events(&r) == r);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let (mut p, q) = IntervalTreeClock::new().fork();
+        let (mut q, mut r) = q.fork();
+        p.bump();
+        q.receive(&p.send());
+        r.bump();
+
+        for clock in [&p, &q, &r] {
+            let json: IntervalTreeClock =
+                serde_json::from_str(&serde_json::to_string(clock).unwrap()).unwrap();
+            assert!(json == *clock);
+
+            let config = bincode::config::standard();
+            let bytes = bincode::serde::encode_to_vec(clock, config).unwrap();
+            let (binary, _): (IntervalTreeClock, _) =
+                bincode::serde::decode_from_slice(&bytes, config).unwrap();
+            assert!(binary == *clock);
+        }
+
+        // Ids survive the round-trip too, so the deserialized clock carries on where it left off.
+        let mut decoded: IntervalTreeClock =
+            serde_json::from_str(&serde_json::to_string(&q).unwrap()).unwrap();
+        let message = r.send();
+        decoded.receive(&message);
+        q.receive(&message);
+        assert!(decoded == q);
+        assert!(decoded > p);
+
+        // Trees that aren't normalized are rejected.
+        let unnormalized = r#"{"id":"Full","event":{"Split":[0,{"N":1},{"N":1}]}}"#;
+        assert!(serde_json::from_str::<IntervalTreeClock>(unnormalized).is_err());
+        let unnormalized = r#"{"id":{"Split":["Full","Full"]},"event":{"N":0}}"#;
+        assert!(serde_json::from_str::<IntervalTreeClock>(unnormalized).is_err());
+    }
+}
@@ -1,3 +1,29 @@
+use std::cmp::Ordering;
+
+/// The causal relationship between two events, as told by their clocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Causality {
+    /// The first event happens before the second, i.e. `a -> b`.
+    Before,
+    /// The second event happens before the first, i.e. `b -> a`.
+    After,
+    /// Both clocks represent the same state.
+    Equal,
+    /// Neither event happens before the other, i.e. `a || b`.
+    Concurrent,
+}
+
+impl From<Option<Ordering>> for Causality {
+    fn from(ordering: Option<Ordering>) -> Self {
+        match ordering {
+            Some(Ordering::Less) => Causality::Before,
+            Some(Ordering::Greater) => Causality::After,
+            Some(Ordering::Equal) => Causality::Equal,
+            None => Causality::Concurrent,
+        }
+    }
+}
+
 pub trait LamportClock: PartialOrd {
     /// Updates this clock for when its respective process executes a local event.
     fn bump(&mut self);
@@ -10,34 +36,135 @@ pub trait LamportClock: PartialOrd {
     /// Signifies a process receiving a message from another process, updating the clock's state
     /// with the sender's clock that is piggybacked onto the received message.
     fn receive(&mut self, incoming_clock: &Self);
+
+    /// Returns the causal relationship between the events this clock and `other` were taken at.
+    ///
+    /// Note that clocks upholding only the Clock Condition (e.g. hybrid logical clocks) totally
+    /// order events, so they never report concurrency: for those, `Before` only means that the
+    /// first event *may* happen before the second, but certainly not the other way around.
+    fn causal_cmp(&self, other: &Self) -> Causality {
+        Causality::from(self.partial_cmp(other))
+    }
 }
 
+/// Lamport's scalar logical clocks, totally ordered by counter and then by process id.
+pub mod scalar_lamport_clock;
+
 /// The (Lamport) Clock Condition gives that if `a` happens before `b` (denoted `a -> b`), then
 /// `TS(a) < TS(b)`. Vector clocks guarantee a stronger condition: `a -> b` <=> `TS(a) < TS(b)`.
 pub mod vector_clock;
 
+/// Dotted version vectors track concurrent values of keys in replicated stores, without the false
+/// conflicts of plain version vectors.
+pub mod dotted_version_vector;
+
+/// Matrix clocks track what every process knows every other process to know, e.g. to tell which
+/// log entries have been seen everywhere.
+pub mod matrix_clock;
+
+/// Exchanges vector clocks between processes by sending only the entries that changed.
+pub mod vector_clock_channel;
+
+/// Vector clocks over a fixed set of processes, stored densely for fast merges and comparisons.
+pub mod dense_vector_clock;
+
+/// Delivers broadcast messages in causal order, holding back those that arrive too early.
+pub mod causal_broadcast;
+
+/// Multicasts messages to a fixed group, delivering them in the same order everywhere.
+pub mod total_order;
+
+/// A lock shared by a fixed group of processes, ordering requests by their clocks.
+pub mod mutex;
+
+/// Records consistent global snapshots of a running system, stamped with vector clocks.
+pub mod snapshot;
+
+/// Enumerates the consistent cuts of vector-clock-stamped traces, and detects global predicates
+/// that possibly or definitely held.
+pub mod trace;
+
+/// Exports vector-clock-stamped executions to ShiViz and Graphviz for visualization.
+pub mod export;
+
+/// Tracks which events every replica has seen, i.e. that are causally stable.
+pub mod stability;
+
+/// Varints and the errors shared by the clocks' compact binary encodings.
+pub mod codec;
+
 /// Hybrid logical time clocks preserve the Clock Condition, i.e. `a -> b` => `TS(a) < TS(b)`; and
-/// are backwards-compatible with NTC. An HLC can be represented as a 64-bit float! Very cool.
+/// are backwards-compatible with NTP. Timestamps pack into a single 64-bit integer at millisecond
+/// or microsecond resolution, or 128 bits at nanosecond resolution, ordered like the timestamps.
 pub mod hybrid_logical_clock;
 
+/// Read-uncertainty restarts and commit-wait for transactions timestamped by hybrid logical
+/// clocks, e.g. in multi-version concurrency control.
+pub mod transaction;
+
+/// A centralized timestamp oracle handing out batches of packed HLC timestamps, and its client.
+pub mod tso;
+
+/// Merges per-node streams of HLC-timestamped entries into one causally-consistent stream.
+pub mod merge;
+
 /// Provides causality tracking in dynamic settings, e.g. peer-to-peer systems. Generalizes vector
 /// clocks and version vectors to a clock whose space requirement scales reasonably with the
 /// number of entities and grows modestly over time.
-mod interval_tree_clock;
+pub mod interval_tree_clock;
+
+/// Helpers for the randomized simulations the protocols are tested with.
+#[cfg(test)]
+mod simulation;
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::hybrid_logical_clock::HybridLogicalClock;
+    use crate::dense_vector_clock::DenseVectorClock;
+    use crate::hybrid_logical_clock::{HybridLogicalClock, SystemClock};
     use crate::interval_tree_clock::IntervalTreeClock;
+    use crate::matrix_clock::MatrixClock;
+    use crate::scalar_lamport_clock::ScalarLamportClock;
     use crate::vector_clock::VectorClock;
 
     #[test]
     fn they_are_lamport_clocks() {
         fn assert_impl<T: LamportClock>() {}
         // Will fail to compile if the given types don't implement the LamportClock trait.
+        assert_impl::<ScalarLamportClock>();
         assert_impl::<VectorClock>();
+        assert_impl::<VectorClock<String, u64>>();
+        assert_impl::<DenseVectorClock<64>>();
+        assert_impl::<MatrixClock<String, u64>>();
         assert_impl::<HybridLogicalClock>();
         assert_impl::<IntervalTreeClock>();
     }
+
+    #[test]
+    fn test_causal_cmp() {
+        fn assert_causality<T: LamportClock>(mut p: T, mut q: T) {
+            assert_eq!(p.causal_cmp(&p), Causality::Equal);
+            let message = p.send();
+            q.receive(&message);
+            assert_eq!(message.causal_cmp(&q), Causality::Before);
+            assert_eq!(q.causal_cmp(&message), Causality::After);
+        }
+
+        assert_causality(ScalarLamportClock::new(1), ScalarLamportClock::new(2));
+        assert_causality(VectorClock::<_, usize>::new(1), VectorClock::new(2));
+        let (p, q) = IntervalTreeClock::new().fork();
+        assert_causality(p, q);
+        let clock = || HybridLogicalClock::new().with_time_source(SystemClock);
+        assert_causality(clock(), clock());
+
+        // Only clocks that characterize causality can tell that events are concurrent.
+        let (mut p, mut q) = (VectorClock::<_, usize>::new(1), VectorClock::new(2));
+        p.bump();
+        q.bump();
+        assert_eq!(p.causal_cmp(&q), Causality::Concurrent);
+        let (mut p, mut q) = IntervalTreeClock::new().fork();
+        p.bump();
+        q.bump();
+        assert_eq!(p.causal_cmp(&q), Causality::Concurrent);
+    }
 }
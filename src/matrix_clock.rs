@@ -0,0 +1,179 @@
+//! Matrix clocks track not only what a process knows of every other process's progress (as its
+//! vector clock does), but also what it knows every other process to know. Process `i` keeps a
+//! row per process: row `i` is its own vector clock, and row `k` is the latest of `k`'s vector
+//! clocks that `i` has (transitively) heard of.
+//!
+//! The minimum of an entry over all rows is then a lower bound on what every process knows of
+//! the entry's process; e.g. log entries up to it have been seen by everyone, and can be
+//! garbage-collected.
+
+use crate::LamportClock;
+use crate::vector_clock::VectorClock;
+use num_traits::CheckedAdd;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct MatrixClock<K = usize, V = usize>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    /// `rows[k]` is what the owning process knows process `k` to know, i.e. `k`'s vector clock
+    /// as of the latest of its events the owner has heard of.
+    rows: HashMap<K, VectorClock<K, V>>,
+    /// The process who owns this matrix clock.
+    i: K,
+}
+
+impl<K, V> MatrixClock<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    /// Constructs a new matrix clock for process `i` in a system of the given processes (which
+    /// `i` is taken to be one of).
+    pub fn new(i: K, processes: impl IntoIterator<Item = K>) -> Self {
+        let mut rows: HashMap<_, _> = processes
+            .into_iter()
+            .map(|k| (k.clone(), VectorClock::new(k)))
+            .collect();
+        rows.entry(i.clone())
+            .or_insert_with(|| VectorClock::new(i.clone()));
+        Self { rows, i }
+    }
+
+    /// The owning process's own vector clock.
+    pub fn vector_clock(&self) -> &VectorClock<K, V> {
+        &self.rows[&self.i]
+    }
+
+    /// What the owning process knows process `k` to know, if `k` is part of the system.
+    pub fn row(&self, k: &K) -> Option<&VectorClock<K, V>> {
+        self.rows.get(k)
+    }
+
+    /// Increments the owning process's own entry.
+    pub fn bump(&mut self) {
+        self.own_row().bump();
+    }
+
+    /// Bumps the clock, and returns a copy of it to be piggybacked onto a message.
+    pub fn send(&mut self) -> Self {
+        self.bump();
+        self.clone()
+    }
+
+    /// Merges the clock piggybacked onto a received message into this one, and bumps it. Besides
+    /// taking the entry-wise maximum of every row, the receiver now knows everything the sender
+    /// did, so the sender's own row is merged into the receiver's.
+    pub fn receive(&mut self, incoming_clock: &Self) {
+        for (k, row) in &incoming_clock.rows {
+            self.rows
+                .entry(k.clone())
+                .or_insert_with(|| VectorClock::new(k.clone()))
+                .merge(row);
+        }
+        let sender = incoming_clock.vector_clock();
+        self.own_row().merge(sender);
+        self.bump();
+    }
+
+    /// Returns, for each process, the number of its events that every process is known to know
+    /// of. Entries that aren't known everywhere are left at the default value.
+    pub fn min_known(&self) -> VectorClock<K, V> {
+        let mut min_known = VectorClock::new(self.i.clone());
+        // No process is known to know more than the owner itself does.
+        for (k, v) in self.vector_clock().clock.iter() {
+            let min = self
+                .rows
+                .values()
+                .map(|row| row.get(k))
+                .fold(v.clone(), Ord::min);
+            if min != V::default() {
+                Arc::make_mut(&mut min_known.clock).insert(k.clone(), min);
+            }
+        }
+        min_known
+    }
+
+    fn own_row(&mut self) -> &mut VectorClock<K, V> {
+        self.rows
+            .get_mut(&self.i)
+            .expect("the owner's row is always present")
+    }
+}
+
+impl<K, V> LamportClock for MatrixClock<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    fn bump(&mut self) {
+        MatrixClock::bump(self);
+    }
+
+    fn send(&mut self) -> Self {
+        MatrixClock::send(self)
+    }
+
+    fn receive(&mut self, incoming_clock: &Self) {
+        MatrixClock::receive(self, incoming_clock);
+    }
+}
+
+/// Matrix clocks are compared by the owning processes' own vector clocks, i.e. by the events
+/// they were taken at.
+impl<K, V> PartialEq for MatrixClock<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.vector_clock() == other.vector_clock()
+    }
+}
+
+impl<K, V> PartialOrd for MatrixClock<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.vector_clock().partial_cmp(other.vector_clock())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_known() {
+        let processes = [1, 2, 3];
+        let [mut p1, mut p2, mut p3] =
+            processes.map(|i| MatrixClock::<usize, usize>::new(i, processes));
+
+        // p1 -> p2: p2 doesn't know whether p3 knows anything yet.
+        let message = p1.send();
+        p2.receive(&message);
+        assert_eq!(p2.min_known(), VectorClock::new(2));
+        assert!(message < p2);
+
+        // p2 -> p3: p3 knows that everyone knows of p1's event, but not of p2's.
+        p3.receive(&p2.send());
+        assert_eq!(p3.min_known().get(&1), 1);
+        assert_eq!(p3.min_known().get(&2), 0);
+
+        // p3 -> p1: p1 learns that p3 knows of p2's events too, but not whether p2 knows of
+        // p3's.
+        p1.receive(&p3.send());
+        assert_eq!(p1.row(&3), Some(p3.vector_clock()));
+        let min_known = p1.min_known();
+        assert_eq!(min_known.get(&1), 1);
+        assert_eq!(min_known.get(&2), 2);
+        assert_eq!(min_known.get(&3), 0);
+    }
+}
@@ -0,0 +1,206 @@
+//! Merges streams of HLC-timestamped entries (e.g. per-node logs), each sorted by its own node's
+//! clock, into a single stream ordered by HLC timestamp. Since `e -> f => L(e) < L(f)`, the merged
+//! stream is consistent with causality: no entry ever comes before one that happened before it.
+//!
+//! Entries with equal timestamps (necessarily concurrent, or duplicates) are ordered by the index
+//! of their stream, and then by their order within it, so that merging is deterministic.
+
+use crate::hybrid_logical_clock::{HlcTimestamp, Micros, Resolution};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+
+/// An entry of the merged stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Merged<T, R: Resolution = Micros> {
+    /// The index of the stream the entry came from.
+    pub stream: usize,
+    pub timestamp: HlcTimestamp<R>,
+    pub value: T,
+}
+
+/// An entry whose timestamp is lower than that of the entry before it in the same stream, which
+/// can therefore not be merged in causal order. It is reported as soon as it is encountered, and
+/// otherwise skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutOfOrder<T, R: Resolution = Micros> {
+    pub stream: usize,
+    /// The timestamp of the preceding entry in the stream.
+    pub previous: HlcTimestamp<R>,
+    pub timestamp: HlcTimestamp<R>,
+    pub value: T,
+}
+
+/// The head of one of the streams being merged.
+struct Head<T, R: Resolution> {
+    timestamp: HlcTimestamp<R>,
+    stream: usize,
+    value: T,
+}
+
+impl<T, R: Resolution> Head<T, R> {
+    fn key(&self) -> (HlcTimestamp<R>, usize) {
+        (self.timestamp, self.stream)
+    }
+}
+
+impl<T, R: Resolution> PartialEq for Head<T, R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T, R: Resolution> Eq for Head<T, R> {}
+
+impl<T, R: Resolution> PartialOrd for Head<T, R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T, R: Resolution> Ord for Head<T, R> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Iterator adapter returned by [`merge_by_hlc`].
+pub struct MergeByHlc<I, T, R: Resolution = Micros> {
+    streams: Vec<I>,
+    /// The timestamp of the last entry taken from each stream.
+    last: Vec<Option<HlcTimestamp<R>>>,
+    /// Holds (at most) one entry per stream, namely the next one it has to offer.
+    heads: BinaryHeap<Reverse<Head<T, R>>>,
+    out_of_order: VecDeque<OutOfOrder<T, R>>,
+}
+
+/// Merges the given streams, each of which must be sorted by timestamp, into one. Entries that
+/// violate the order of their own stream are reported as errors in the merged stream.
+pub fn merge_by_hlc<S, T, R>(streams: S) -> MergeByHlc<<S::Item as IntoIterator>::IntoIter, T, R>
+where
+    S: IntoIterator,
+    S::Item: IntoIterator<Item = (HlcTimestamp<R>, T)>,
+    R: Resolution,
+{
+    let streams: Vec<_> = streams.into_iter().map(IntoIterator::into_iter).collect();
+    let mut merge = MergeByHlc {
+        last: vec![None; streams.len()],
+        heads: BinaryHeap::with_capacity(streams.len()),
+        out_of_order: VecDeque::new(),
+        streams,
+    };
+    for stream in 0..merge.streams.len() {
+        merge.advance(stream);
+    }
+    merge
+}
+
+impl<I, T, R> MergeByHlc<I, T, R>
+where
+    I: Iterator<Item = (HlcTimestamp<R>, T)>,
+    R: Resolution,
+{
+    /// Pulls the next in-order entry of `stream` onto the heap, setting aside any out-of-order
+    /// ones on the way.
+    fn advance(&mut self, stream: usize) {
+        for (timestamp, value) in self.streams[stream].by_ref() {
+            match self.last[stream] {
+                Some(previous) if timestamp < previous => {
+                    self.out_of_order.push_back(OutOfOrder {
+                        stream,
+                        previous,
+                        timestamp,
+                        value,
+                    });
+                }
+                _ => {
+                    self.last[stream] = Some(timestamp);
+                    self.heads.push(Reverse(Head {
+                        timestamp,
+                        stream,
+                        value,
+                    }));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl<I, T, R> Iterator for MergeByHlc<I, T, R>
+where
+    I: Iterator<Item = (HlcTimestamp<R>, T)>,
+    R: Resolution,
+{
+    type Item = Result<Merged<T, R>, OutOfOrder<T, R>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(out_of_order) = self.out_of_order.pop_front() {
+            return Some(Err(out_of_order));
+        }
+        let Reverse(head) = self.heads.pop()?;
+        self.advance(head.stream);
+        Some(Ok(Merged {
+            stream: head.stream,
+            timestamp: head.timestamp,
+            value: head.value,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let ts = |l, c| HlcTimestamp::<Micros>::new(l, c);
+        let streams = vec![
+            vec![
+                (ts(1, 0), "a1"),
+                (ts(3, 0), "a2"),
+                (ts(3, 0), "a3"),
+                (ts(7, 1), "a4"),
+            ],
+            vec![],
+            vec![
+                (ts(2, 0), "c1"),
+                (ts(3, 0), "c2"),
+                (ts(1, 5), "c3"),
+                (ts(7, 0), "c4"),
+            ],
+            vec![(ts(0, 9), "d1")],
+        ];
+
+        let (mut merged, mut out_of_order) = (Vec::new(), Vec::new());
+        for entry in merge_by_hlc(streams) {
+            match entry {
+                Ok(entry) => merged.push((entry.stream, entry.value)),
+                Err(entry) => out_of_order.push(entry),
+            }
+        }
+
+        assert_eq!(
+            merged,
+            [
+                (3, "d1"),
+                (0, "a1"),
+                (2, "c1"),
+                // Ties are broken by stream, and then by position within the stream.
+                (0, "a2"),
+                (0, "a3"),
+                (2, "c2"),
+                (2, "c4"),
+                (0, "a4"),
+            ]
+        );
+        assert_eq!(
+            out_of_order,
+            [OutOfOrder {
+                stream: 2,
+                previous: ts(3, 0),
+                timestamp: ts(1, 5),
+                value: "c3",
+            }]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Ricart–Agrawala mutual exclusion: a lock shared by a fixed group of processes, without a
+//! coordinator.
+//!
+//! A process that wants the lock timestamps its request, and asks every other process for
+//! permission. A process grants permission straight away unless it holds the lock, or wants it
+//! itself with an earlier request (timestamps ordered totally, ties broken by process), in which
+//! case it defers its reply until it releases the lock. A process holds the lock once every other
+//! process has replied, which takes `2 * (n - 1)` messages per acquisition, over channels that may
+//! reorder messages but mustn't lose them.
+//!
+//! The algorithm is a pure state machine: local requests and received messages go in, and
+//! messages to send and grants of the lock come out, leaving the transport to the caller.
+//!
+//! See "An Optimal Algorithm for Mutual Exclusion in Computer Networks" by Ricart and Agrawala.
+
+use crate::LamportClock;
+use crate::scalar_lamport_clock::ScalarLamportClock;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// A message from one process of the group to another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Message<C> {
+    /// A request for permission to take the lock, with its timestamp.
+    Request(C),
+    /// Permission to take the lock, in reply to the recipient's latest request.
+    Reply,
+}
+
+/// What the state machine asks of its caller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Output<P, C> {
+    /// Send the message to the given process.
+    Send(P, Message<C>),
+    /// The lock is now held, until it's released.
+    Granted,
+}
+
+#[derive(Clone, Debug)]
+enum State<P, C> {
+    Released,
+    /// Waiting for the replies to the request with the given timestamp.
+    Wanted {
+        timestamp: C,
+        replied: HashSet<P>,
+    },
+    Held,
+}
+
+/// One process's end of a lock shared by a fixed group of processes.
+#[derive(Clone, Debug)]
+pub struct RicartAgrawala<P, C = ScalarLamportClock<P>> {
+    pid: P,
+    clock: C,
+    peers: Vec<P>,
+    state: State<P, C>,
+    /// The processes whose requests are waiting for this process to release the lock.
+    deferred: Vec<P>,
+}
+
+impl<P: Ord + Hash + Clone> RicartAgrawala<P> {
+    /// Constructs process `pid`'s end of the lock shared by the given processes (which `pid` is
+    /// taken to be one of), timestamping requests with a [`ScalarLamportClock`].
+    pub fn new(pid: P, group: impl IntoIterator<Item = P>) -> Self {
+        Self::with_clock(pid.clone(), ScalarLamportClock::new(pid), group)
+    }
+}
+
+impl<P, C> RicartAgrawala<P, C>
+where
+    P: Ord + Hash + Clone,
+    C: LamportClock + Ord + Clone,
+{
+    /// Constructs process `pid`'s end of the lock shared by the given processes, timestamping
+    /// requests with the given clock. The clock's order has to be total, and consistent with
+    /// causality.
+    pub fn with_clock(pid: P, clock: C, group: impl IntoIterator<Item = P>) -> Self {
+        let mut peers: Vec<P> = group.into_iter().filter(|k| *k != pid).collect();
+        peers.sort();
+        peers.dedup();
+        Self {
+            pid,
+            clock,
+            peers,
+            state: State::Released,
+            deferred: Vec::new(),
+        }
+    }
+
+    /// Returns whether this process holds the lock.
+    pub fn is_held(&self) -> bool {
+        matches!(self.state, State::Held)
+    }
+
+    /// Returns whether this process is waiting for the lock.
+    pub fn is_wanted(&self) -> bool {
+        matches!(self.state, State::Wanted { .. })
+    }
+
+    /// Requests the lock. Does nothing if this process already holds it or is waiting for it.
+    pub fn request(&mut self) -> Vec<Output<P, C>> {
+        if !matches!(self.state, State::Released) {
+            return Vec::new();
+        }
+        let timestamp = self.clock.send();
+        let outputs = self
+            .peers
+            .iter()
+            .map(|k| Output::Send(k.clone(), Message::Request(timestamp.clone())))
+            .collect();
+        self.state = State::Wanted {
+            timestamp,
+            replied: HashSet::new(),
+        };
+        self.grant_if_replied(outputs)
+    }
+
+    /// Releases the lock, replying to the deferred requests. Does nothing unless this process
+    /// holds the lock.
+    pub fn release(&mut self) -> Vec<Output<P, C>> {
+        if !self.is_held() {
+            return Vec::new();
+        }
+        self.state = State::Released;
+        self.deferred
+            .drain(..)
+            .map(|k| Output::Send(k, Message::Reply))
+            .collect()
+    }
+
+    /// Handles a message received from process `from`. Messages from processes outside the group
+    /// are ignored.
+    pub fn on_message(&mut self, from: P, message: Message<C>) -> Vec<Output<P, C>> {
+        if !self.peers.contains(&from) {
+            return Vec::new();
+        }
+        match message {
+            Message::Request(timestamp) => {
+                self.clock.receive(&timestamp);
+                let defer = match &self.state {
+                    State::Released => false,
+                    State::Wanted {
+                        timestamp: ours, ..
+                    } => (ours, &self.pid) < (&timestamp, &from),
+                    State::Held => true,
+                };
+                if defer {
+                    self.deferred.push(from);
+                    Vec::new()
+                } else {
+                    vec![Output::Send(from, Message::Reply)]
+                }
+            }
+            Message::Reply => {
+                if let State::Wanted { replied, .. } = &mut self.state {
+                    replied.insert(from);
+                }
+                self.grant_if_replied(Vec::new())
+            }
+        }
+    }
+
+    fn grant_if_replied(&mut self, mut outputs: Vec<Output<P, C>>) -> Vec<Output<P, C>> {
+        if let State::Wanted { replied, .. } = &self.state
+            && replied.len() == self.peers.len()
+        {
+            self.state = State::Held;
+            outputs.push(Output::Granted);
+        }
+        outputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Rng;
+
+    /// Simulates processes that each take the lock a number of times, over a network that
+    /// delivers messages in random order.
+    fn simulate(seed: u64, processes: usize, rounds: usize) {
+        let mut rng = Rng::new(seed);
+        let mut members: Vec<_> = (0..processes)
+            .map(|i| RicartAgrawala::new(i, 0..processes))
+            .collect();
+        let mut remaining = vec![rounds; processes];
+        let mut granted = vec![0; processes];
+        // Messages in flight, as (destination, sender, message).
+        let mut network: Vec<(usize, usize, Message<ScalarLamportClock<usize>>)> = Vec::new();
+
+        while granted.iter().any(|g| *g < rounds) || !network.is_empty() {
+            // The processes that can take a step, and the network if it can.
+            let mut enabled: Vec<_> = (0..processes)
+                .filter(|&i| members[i].is_held() || remaining[i] > 0 && !members[i].is_wanted())
+                .map(Some)
+                .collect();
+            if !network.is_empty() {
+                enabled.push(None);
+            }
+            assert!(!enabled.is_empty(), "seed {seed}: deadlock");
+
+            let (i, outputs) = match enabled[rng.below(enabled.len())] {
+                Some(i) if members[i].is_held() => (i, members[i].release()),
+                Some(i) => {
+                    remaining[i] -= 1;
+                    (i, members[i].request())
+                }
+                None => {
+                    let (to, from, message) = rng.take(&mut network);
+                    (to, members[to].on_message(from, message))
+                }
+            };
+            for output in outputs {
+                match output {
+                    Output::Send(to, message) => network.push((to, i, message)),
+                    Output::Granted => granted[i] += 1,
+                }
+            }
+            let holders = members.iter().filter(|m| m.is_held()).count();
+            assert!(holders <= 1, "seed {seed}: {holders} holders");
+        }
+    }
+
+    #[test]
+    fn test_random_schedules() {
+        for seed in 1..=200 {
+            simulate(seed, 4, 5);
+        }
+        simulate(1, 1, 3);
+    }
+
+    #[test]
+    fn test_earlier_request_wins() {
+        let group = ["a", "b"];
+        let [mut a, mut b] = group.map(|i| RicartAgrawala::new(i, group));
+        // Concurrent requests with equal counters, so a's goes first.
+        let from_a = a.request();
+        let from_b = b.request();
+        assert_eq!(
+            from_a,
+            [Output::Send(
+                "b",
+                Message::Request(ScalarLamportClock::new("a").send())
+            )]
+        );
+        let Output::Send(_, to_b) = &from_a[0] else {
+            unreachable!()
+        };
+        let Output::Send(_, to_a) = &from_b[0] else {
+            unreachable!()
+        };
+
+        assert!(a.on_message("b", to_a.clone()).is_empty());
+        let reply = b.on_message("a", to_b.clone());
+        assert_eq!(reply, [Output::Send("a", Message::Reply)]);
+        assert_eq!(a.on_message("b", Message::Reply), [Output::Granted]);
+        assert!(a.is_held() && b.is_wanted());
+
+        // Requests while holding the lock don't do anything.
+        assert!(a.request().is_empty());
+        assert_eq!(a.release(), [Output::Send("b", Message::Reply)]);
+        assert_eq!(b.on_message("a", Message::Reply), [Output::Granted]);
+        assert!(a.release().is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+//! Lamport's original logical clock: a single counter per process, which is incremented on every
+//! event and fast-forwarded past the counter piggybacked onto every received message. It upholds
+//! the Clock Condition, `a -> b => C(a) < C(b)`, and nothing more, but costs a single integer.
+//!
+//! Breaking ties between equal counters by process id extends the partial order into a total
+//! order that's consistent with causality, which is what e.g. Lamport's mutual exclusion
+//! algorithm and total-order broadcast are built on. Clocks are ordered by counter and then by
+//! process id; packing them into an integer preserves that order.
+//!
+//! See "Time, Clocks, and the Ordering of Events in a Distributed System" by Leslie Lamport.
+
+use crate::LamportClock;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScalarLamportClock<P = u32> {
+    /// The number of events the owning process knows to precede its latest one. Declared first,
+    /// so that the derived order compares it before the process id.
+    counter: u64,
+    /// The process who owns this clock.
+    pid: P,
+}
+
+impl<P: Ord + Clone> ScalarLamportClock<P> {
+    /// Constructs a new clock for the given process identifier.
+    pub fn new(pid: P) -> Self {
+        Self { counter: 0, pid }
+    }
+
+    /// Constructs a clock as of the given counter, e.g. a timestamp received off the wire.
+    pub fn with_counter(pid: P, counter: u64) -> Self {
+        Self { counter, pid }
+    }
+
+    pub fn counter(&self) -> u64 {
+        self.counter
+    }
+
+    pub fn pid(&self) -> &P {
+        &self.pid
+    }
+
+    /// Increments the counter for a local event.
+    pub fn bump(&mut self) {
+        self.counter += 1;
+    }
+
+    /// Bumps the clock, and returns a copy of it to be piggybacked onto a message.
+    pub fn send(&mut self) -> Self {
+        self.bump();
+        self.clone()
+    }
+
+    /// Moves the counter past the one piggybacked onto a received message.
+    pub fn receive(&mut self, incoming_clock: &Self) {
+        self.counter = self.counter.max(incoming_clock.counter);
+        self.bump();
+    }
+}
+
+impl<P: Ord + Clone> LamportClock for ScalarLamportClock<P> {
+    fn bump(&mut self) {
+        ScalarLamportClock::bump(self);
+    }
+
+    fn send(&mut self) -> Self {
+        ScalarLamportClock::send(self)
+    }
+
+    fn receive(&mut self, incoming_clock: &Self) {
+        ScalarLamportClock::receive(self, incoming_clock);
+    }
+}
+
+macro_rules! impl_packed_conversions {
+    ($($pid:ty => $packed:ty),*) => {$(
+        /// Packs the clock as `[ counter | pid ]`, in that order, so that packed clocks compare
+        /// like the clocks themselves. Panics if the counter doesn't fit next to the pid.
+        impl From<ScalarLamportClock<$pid>> for $packed {
+            fn from(clock: ScalarLamportClock<$pid>) -> $packed {
+                const PID_BITS: u32 = <$pid>::BITS;
+                assert!(
+                    (clock.counter as $packed) < 1 << (<$packed>::BITS - PID_BITS),
+                    "counter does not fit in {} bits",
+                    <$packed>::BITS - PID_BITS
+                );
+                ((clock.counter as $packed) << PID_BITS) | clock.pid as $packed
+            }
+        }
+
+        impl From<$packed> for ScalarLamportClock<$pid> {
+            fn from(packed: $packed) -> Self {
+                Self::with_counter(packed as $pid, (packed >> <$pid>::BITS) as u64)
+            }
+        }
+    )*};
+}
+
+impl_packed_conversions!(u16 => u64, u32 => u128, u64 => u128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total_order() {
+        let (mut p1, mut p2) = (
+            ScalarLamportClock::<u16>::new(1),
+            ScalarLamportClock::new(2),
+        );
+        p1.bump();
+        p2.bump();
+        // Concurrent events with equal counters are ordered by process id.
+        assert!(p1 < p2);
+
+        let message = p2.send();
+        p1.receive(&message);
+        assert_eq!(p1.counter(), 3);
+        assert!(message < p1);
+
+        let packed: Vec<u64> = [p1, p2, message].into_iter().map(u64::from).collect();
+        assert_eq!(packed, [3 << 16 | 1, 2 << 16 | 2, 2 << 16 | 2]);
+        assert!(packed[1] < packed[0]);
+        assert_eq!(ScalarLamportClock::from(packed[0]), p1);
+
+        let wide = ScalarLamportClock::<u64>::with_counter(u64::MAX, 7);
+        assert_eq!(ScalarLamportClock::from(u128::from(wide)), wide);
+        // A counter too wide to pack would lose its high bits, and with them the order.
+        let overflowing = ScalarLamportClock::<u16>::with_counter(1, 1 << 48);
+        assert!(std::panic::catch_unwind(|| u64::from(overflowing)).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+//! The simulations model the network as a `Vec` of the messages in flight, which get delivered
+//! (or dropped, or duplicated) in an order drawn from a seeded [`Rng`].
+
+/// A deterministic xorshift generator, so that failures can be reproduced from the seed.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Panics if the seed is zero, which xorshift never leaves.
+    pub(crate) fn new(seed: u64) -> Self {
+        assert_ne!(seed, 0, "xorshift needs a nonzero seed");
+        Self(seed)
+    }
+
+    /// Returns a number in `0..n`.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+
+    /// Removes a random message from those in flight, i.e. delivers them in random order.
+    pub(crate) fn take<T>(&mut self, network: &mut Vec<T>) -> T {
+        network.swap_remove(self.below(network.len()))
+    }
+}
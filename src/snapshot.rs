@@ -0,0 +1,331 @@
+//! Chandy–Lamport snapshots: a consistent global state of a running system, recorded without
+//! stopping it, over FIFO channels.
+//!
+//! The initiator records its own state, and sends a marker down every outgoing channel. A process
+//! receiving the first marker of a snapshot does the same, and records the channel the marker came
+//! on as empty. From then on, until a marker arrives on it, every message received on an incoming
+//! channel was in flight across the cut, and is recorded as the channel's state. A process is done
+//! once markers came in on all its incoming channels.
+//!
+//! Every application message carries the sender's vector clock, and every process's recording is
+//! stamped with its clock at the point it recorded its state, so that the recordings can be checked
+//! to form a consistent cut with [`is_consistent`](crate::snapshot::is_consistent).
+//!
+//! See "Distributed Snapshots: Determining Global States of Distributed Systems" by Chandy and
+//! Lamport.
+
+use crate::vector_clock::VectorClock;
+use num_traits::CheckedAdd;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// A message on a channel between two processes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Message<M, K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    /// An application message, with the sender's clock.
+    App {
+        clock: VectorClock<K, V>,
+        payload: M,
+    },
+    /// The marker of the given snapshot.
+    Marker(u64),
+}
+
+/// One process's part of a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalSnapshot<S, M, K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    /// The snapshot's number.
+    pub id: u64,
+    /// The process's state.
+    pub state: S,
+    /// The process's clock as of its state.
+    pub clock: VectorClock<K, V>,
+    /// For each incoming channel, by sender, the messages that were in flight on it, in the order
+    /// they were sent.
+    pub channels: HashMap<K, InFlight<M, K, V>>,
+}
+
+/// The messages recorded on a channel, with their senders' clocks.
+pub type InFlight<M, K, V> = Vec<(VectorClock<K, V>, M)>;
+
+/// What the state machine asks of its caller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Output<S, M, K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    /// Send the message down the channel to the given process.
+    Send(K, Message<M, K, V>),
+    /// Hand the payload received from the given process to the application.
+    Deliver(K, M),
+    /// The process's part of the snapshot is complete.
+    Recorded(LocalSnapshot<S, M, K, V>),
+}
+
+/// One process's end of the snapshot protocol.
+///
+/// Snapshots mustn't overlap: a snapshot should only be initiated once every process has recorded
+/// its part of the previous one. Any number of processes may initiate the same snapshot though.
+#[derive(Clone, Debug)]
+pub struct ChandyLamport<S, M, K = usize, V = usize>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    clock: VectorClock<K, V>,
+    peers: Vec<K>,
+    /// The number of the latest snapshot this process has taken part in.
+    latest: u64,
+    recording: Option<Recording<S, M, K, V>>,
+}
+
+#[derive(Clone, Debug)]
+struct Recording<S, M, K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    snapshot: LocalSnapshot<S, M, K, V>,
+    /// The incoming channels no marker has come in on yet.
+    open: HashSet<K>,
+}
+
+impl<S, M, K, V> ChandyLamport<S, M, K, V>
+where
+    M: Clone,
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    /// Constructs process `i`'s end of the protocol, with channels to and from each of the given
+    /// processes.
+    pub fn new(i: K, peers: impl IntoIterator<Item = K>) -> Self {
+        let peers = peers.into_iter().filter(|k| *k != i).collect();
+        Self {
+            clock: VectorClock::new(i),
+            peers,
+            latest: 0,
+            recording: None,
+        }
+    }
+
+    pub fn clock(&self) -> &VectorClock<K, V> {
+        &self.clock
+    }
+
+    /// Returns whether this process is recording its part of a snapshot.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Sends an application message to process `to`.
+    pub fn send(&mut self, to: K, payload: M) -> Output<S, M, K, V> {
+        let clock = self.clock.send();
+        Output::Send(to, Message::App { clock, payload })
+    }
+
+    /// Initiates the next snapshot, recording the given state as this process's. Does nothing
+    /// while this process is recording its part of a snapshot already.
+    pub fn initiate(&mut self, state: S) -> Vec<Output<S, M, K, V>> {
+        if self.is_recording() {
+            return Vec::new();
+        }
+        self.record(self.latest + 1, state)
+    }
+
+    /// Handles a message received from process `from`, where `state` returns this process's
+    /// state, should it be recorded.
+    pub fn on_message(
+        &mut self,
+        from: K,
+        message: Message<M, K, V>,
+        state: impl FnOnce() -> S,
+    ) -> Vec<Output<S, M, K, V>> {
+        match message {
+            Message::App { clock, payload } => {
+                self.clock.receive(&clock);
+                if let Some(recording) = &mut self.recording
+                    && recording.open.contains(&from)
+                {
+                    let channel = recording.snapshot.channels.entry(from.clone());
+                    channel.or_default().push((clock, payload.clone()));
+                }
+                vec![Output::Deliver(from, payload)]
+            }
+            Message::Marker(id) => {
+                let mut outputs = Vec::new();
+                if self.recording.is_none() && id > self.latest {
+                    outputs = self.record(id, state());
+                }
+                let Some(recording) = &mut self.recording else {
+                    return outputs;
+                };
+                if recording.snapshot.id == id {
+                    recording.open.remove(&from);
+                    if recording.open.is_empty() {
+                        let recording = self.recording.take().expect("recording is in progress");
+                        outputs.push(Output::Recorded(recording.snapshot));
+                    }
+                }
+                outputs
+            }
+        }
+    }
+
+    fn record(&mut self, id: u64, state: S) -> Vec<Output<S, M, K, V>> {
+        self.latest = id;
+        let snapshot = LocalSnapshot {
+            id,
+            state,
+            clock: self.clock.clone(),
+            channels: HashMap::new(),
+        };
+        let mut outputs: Vec<_> = self
+            .peers
+            .iter()
+            .map(|k| Output::Send(k.clone(), Message::Marker(id)))
+            .collect();
+        if self.peers.is_empty() {
+            outputs.push(Output::Recorded(snapshot));
+        } else {
+            let open = self.peers.iter().cloned().collect();
+            self.recording = Some(Recording { snapshot, open });
+        }
+        outputs
+    }
+}
+
+/// Returns whether the processes' recordings form a consistent cut: no process's state reflects a
+/// message that, as of the other recordings, wasn't sent yet, and nor was any message recorded in
+/// flight sent after the cut.
+pub fn is_consistent<S, M, K, V>(snapshots: &[LocalSnapshot<S, M, K, V>]) -> bool
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    let cut: HashMap<&K, &VectorClock<K, V>> = snapshots
+        .iter()
+        .map(|snapshot| (snapshot.clock.owner(), &snapshot.clock))
+        .collect();
+    // The number of events of `k` before the cut.
+    let events = |k: &K| cut.get(k).map(|clock| clock.get(k)).unwrap_or_default();
+
+    snapshots.iter().all(|snapshot| {
+        let seen_sent = snapshot
+            .clock
+            .iter()
+            .all(|(k, v)| *v <= events(k) || !cut.contains_key(k));
+        let in_flight = snapshot.channels.iter().all(|(sender, messages)| {
+            messages
+                .iter()
+                .all(|(clock, _)| clock.get(sender) <= events(sender))
+        });
+        seen_sent && in_flight
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Rng;
+    use std::collections::VecDeque;
+
+    /// Simulates banks that keep transferring money to each other over FIFO channels, while one of
+    /// them initiates a snapshot: the money recorded in the banks and on the channels always adds
+    /// up to the total.
+    fn simulate(seed: u64, banks: usize) {
+        const BALANCE: u64 = 100;
+        let mut rng = Rng::new(seed);
+        let mut processes: Vec<ChandyLamport<u64, u64>> = (0..banks)
+            .map(|i| ChandyLamport::new(i, 0..banks))
+            .collect();
+        let mut balances = vec![BALANCE; banks];
+        // The channel from `i` to `j` is `channels[i * banks + j]`.
+        let mut channels = vec![VecDeque::new(); banks * banks];
+        let mut snapshots = Vec::new();
+        let mut steps = 0;
+
+        while snapshots.len() < banks {
+            steps += 1;
+            let i = rng.below(banks);
+            let incoming: Vec<_> = (0..banks)
+                .filter(|from| !channels[from * banks + i].is_empty())
+                .collect();
+            let mut outputs = Vec::new();
+            if steps == 20 {
+                outputs = processes[i].initiate(balances[i]);
+            } else if rng.below(3) == 0 && steps < 200 {
+                let to = (i + 1 + rng.below(banks - 1)) % banks;
+                let amount = rng.below(10) as u64;
+                balances[i] -= amount;
+                outputs.push(processes[i].send(to, amount));
+            } else if !incoming.is_empty() {
+                let from = incoming[rng.below(incoming.len())];
+                let message = channels[from * banks + i].pop_front().unwrap();
+                outputs = processes[i].on_message(from, message, || balances[i]);
+            }
+
+            for output in outputs {
+                match output {
+                    Output::Send(to, message) => channels[i * banks + to].push_back(message),
+                    Output::Deliver(_, amount) => balances[i] += amount,
+                    Output::Recorded(snapshot) => snapshots.push(snapshot),
+                }
+            }
+        }
+
+        assert!(is_consistent(&snapshots), "seed {seed}");
+        let recorded: u64 = snapshots
+            .iter()
+            .map(|snapshot| {
+                let in_flight = snapshot.channels.values().flatten().map(|(_, a)| a);
+                snapshot.state + in_flight.sum::<u64>()
+            })
+            .sum();
+        assert_eq!(recorded, BALANCE * banks as u64, "seed {seed}");
+        assert!(processes.iter().all(|p| !p.is_recording()), "seed {seed}");
+    }
+
+    #[test]
+    fn test_snapshots_add_up() {
+        for seed in 1..=200 {
+            simulate(seed, 4);
+        }
+    }
+
+    #[test]
+    fn test_inconsistent_cut() {
+        let [mut p, mut q] = [0, 1].map(|i| ChandyLamport::<(), &str>::new(i, [0, 1]));
+        let before = p.clock().clone();
+        let Output::Send(_, message) = p.send(1, "hello") else {
+            unreachable!()
+        };
+        q.on_message(0, message, || ());
+        let snapshot = |clock: &VectorClock| LocalSnapshot {
+            id: 1,
+            state: (),
+            clock: clock.clone(),
+            channels: HashMap::new(),
+        };
+
+        // q's state reflects a message p hadn't sent as of its own.
+        assert!(!is_consistent(&[snapshot(&before), snapshot(q.clock())]));
+        assert!(is_consistent(&[snapshot(p.clock()), snapshot(q.clock())]));
+        // Nor can a message sent after the cut have been in flight across it.
+        let mut in_flight = snapshot(&VectorClock::new(1));
+        in_flight
+            .channels
+            .insert(0, vec![(p.clock().clone(), "hello")]);
+        assert!(is_consistent(&[snapshot(p.clock()), in_flight.clone()]));
+        assert!(!is_consistent(&[snapshot(&before), in_flight]));
+    }
+}
@@ -0,0 +1,165 @@
+//! An event is *causally stable* once every replica has seen it, i.e. once every clock that will
+//! ever be produced is known to be above it: nothing concurrent with it can still show up, so e.g.
+//! operations up to it can be compacted or their metadata dropped.
+//!
+//! Given the latest clock received from every replica, the greatest lower bound (meet) of these
+//! clocks is the *stable frontier*: every event at or below it is stable.
+//!
+//! See "Making Operation-Based CRDTs Operation-Based" by Baquero, Almeida and Shoker.
+
+use crate::interval_tree_clock::IntervalTreeClock;
+use crate::vector_clock::VectorClock;
+use num_traits::CheckedAdd;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Clocks whose states form a lattice, ordered by causality.
+pub trait Lattice: PartialOrd + Clone {
+    /// The least upper bound of the two clocks: everything either has seen.
+    fn join(&self, other: &Self) -> Self;
+
+    /// The greatest lower bound of the two clocks: what both have seen.
+    fn meet(&self, other: &Self) -> Self;
+}
+
+impl<K, V> Lattice for VectorClock<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    fn join(&self, other: &Self) -> Self {
+        VectorClock::join(self, other)
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        VectorClock::meet(self, other)
+    }
+}
+
+impl Lattice for IntervalTreeClock {
+    fn join(&self, other: &Self) -> Self {
+        self.join_events(other)
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        self.meet_events(other)
+    }
+}
+
+/// Tracks the stable frontier of a fixed set of replicas, along with the events that are
+/// waiting to become stable.
+pub struct StabilityTracker<R, C, T> {
+    /// The latest clock seen from each replica, if any yet.
+    replicas: HashMap<R, Option<C>>,
+    frontier: Option<C>,
+    /// Events that aren't stable yet, in the order they were tracked.
+    pending: Vec<(C, T)>,
+}
+
+impl<R: Eq + Hash, C: Lattice, T> StabilityTracker<R, C, T> {
+    /// Constructs a tracker for the given replicas. There's no stable frontier until a clock has
+    /// been seen from every one of them.
+    pub fn new(replicas: impl IntoIterator<Item = R>) -> Self {
+        Self {
+            replicas: replicas.into_iter().map(|r| (r, None)).collect(),
+            frontier: None,
+            pending: Vec::new(),
+        }
+    }
+
+    /// The greatest clock below which every event is stable, once every replica has been seen.
+    pub fn frontier(&self) -> Option<&C> {
+        self.frontier.as_ref()
+    }
+
+    /// Tracks an event (e.g. a delivered operation) with the given clock until it becomes stable.
+    /// Returns it straight away if it already is.
+    pub fn track(&mut self, clock: C, event: T) -> Option<(C, T)> {
+        if self
+            .frontier
+            .as_ref()
+            .is_some_and(|frontier| clock <= *frontier)
+        {
+            return Some((clock, event));
+        }
+        self.pending.push((clock, event));
+        None
+    }
+
+    /// Records a clock received from `replica`, and returns the tracked events that became stable
+    /// as a result, in the order they were tracked. Clocks of unknown replicas are ignored.
+    pub fn observe(&mut self, replica: &R, clock: &C) -> Vec<(C, T)> {
+        let Some(latest) = self.replicas.get_mut(replica) else {
+            return Vec::new();
+        };
+        *latest = Some(match latest {
+            Some(latest) => latest.join(clock),
+            None => clock.clone(),
+        });
+
+        self.frontier = self
+            .replicas
+            .values()
+            .try_fold(None, |frontier: Option<C>, latest| {
+                let latest = latest.as_ref()?;
+                Some(Some(match frontier {
+                    Some(frontier) => frontier.meet(latest),
+                    None => latest.clone(),
+                }))
+            })
+            .flatten();
+        let Some(frontier) = &self.frontier else {
+            return Vec::new();
+        };
+
+        let (stable, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(clock, _)| clock <= frontier);
+        self.pending = pending;
+        stable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LamportClock;
+
+    #[test]
+    fn test_stability() {
+        let [mut p1, mut p2, mut p3] = [1, 2, 3].map(VectorClock::<usize, usize>::new);
+        let mut tracker = StabilityTracker::new([1, 2, 3]);
+
+        let a = p1.send();
+        assert_eq!(tracker.track(a.clone(), "a"), None);
+        p2.receive(&a);
+        let b = p2.send();
+        assert_eq!(tracker.track(b.clone(), "b"), None);
+        p3.receive(&b);
+
+        assert!(tracker.observe(&1, &p1).is_empty());
+        assert!(tracker.observe(&2, &p2).is_empty());
+        assert_eq!(tracker.frontier(), None);
+        // Everyone has now seen a, but p1 hasn't seen b.
+        let stable = tracker.observe(&3, &p3);
+        assert_eq!(stable, [(a.clone(), "a")]);
+        assert_eq!(tracker.frontier(), Some(&a));
+
+        p1.receive(&p3.send());
+        assert_eq!(tracker.observe(&1, &p1), [(b.clone(), "b")]);
+        // Events below the frontier are stable as soon as they're tracked.
+        assert_eq!(tracker.track(a.clone(), "a"), Some((a, "a")));
+        assert!(tracker.observe(&4, &p1).is_empty());
+    }
+
+    #[test]
+    fn test_interval_tree_clock_stability() {
+        let (mut p, mut q) = IntervalTreeClock::new().fork();
+        let mut tracker = StabilityTracker::new(["p", "q"]);
+        let message = p.send();
+        assert!(tracker.track(message.clone(), ()).is_none());
+        assert!(tracker.observe(&"p", &p).is_empty());
+        q.receive(&message);
+        assert_eq!(tracker.observe(&"q", &q).len(), 1);
+    }
+}
@@ -0,0 +1,392 @@
+//! Total-order multicast: every process in a fixed group delivers every message multicast to the
+//! group, and all of them deliver the messages in the same order, which is consistent with
+//! causality. Feeding the messages to deterministic state machines in that order replicates them.
+//!
+//! Messages are ordered by their Lamport timestamps, ties broken by sender. Every process holds
+//! back the messages it receives, and acknowledges each of them to the whole group. A held-back
+//! message is delivered once it's the earliest one held back, and something timestamped after it
+//! has been received from every other process but its sender: as every process's messages are
+//! processed in the order they were sent, nothing timestamped before it can still show up.
+//!
+//! That relies on reliable FIFO channels, which are built here on top of any lossy, duplicating and
+//! reordering transport: each process numbers the messages it sends, receivers process them in
+//! that order, and tell the sender how far they got by piggybacking it onto their own messages.
+//! Messages that don't call for an acknowledgement (acknowledgements themselves, and duplicates)
+//! get an unsequenced receipt instead, so that a quiet group still confirms everything it got.
+//! Messages that a peer isn't known to have received can be sent again, e.g. on a timer, with
+//! [`TotalOrderMulticast::retransmit`](crate::total_order::TotalOrderMulticast::retransmit).
+//!
+//! See "Time, Clocks, and the Ordering of Events in a Distributed System" by Leslie Lamport.
+
+use crate::LamportClock;
+use crate::scalar_lamport_clock::ScalarLamportClock;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// What a message carries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Kind<T> {
+    /// A multicast payload, to be delivered.
+    Data(T),
+    /// An acknowledgement of every message received before it.
+    Ack,
+    /// Confirms how many of the recipient's messages the sender has processed, in reply to a
+    /// message that isn't acknowledged otherwise. Receipts aren't sequenced, nor replied to.
+    Receipt,
+}
+
+/// A message from one process of the group to another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Envelope<P, C, T> {
+    pub sender: P,
+    /// The message's position among those sent by the sender, starting at 1, or 0 for receipts.
+    pub seq: u64,
+    /// The number of the recipient's messages that the sender had processed.
+    pub received: u64,
+    pub timestamp: C,
+    pub kind: Kind<T>,
+}
+
+/// Messages to be sent, each with the process to send it to.
+pub type Outgoing<P, C, T> = Vec<(P, Envelope<P, C, T>)>;
+
+/// What a process knows of another member of the group.
+#[derive(Clone, Debug)]
+struct Peer<C, T> {
+    /// The number of the peer's messages processed so far.
+    processed: u64,
+    /// Messages from the peer that arrived ahead of ones it sent before them, by number.
+    early: BTreeMap<u64, (C, Kind<T>)>,
+    /// The timestamp of the latest message processed from the peer.
+    latest: Option<C>,
+    /// The number of this process's messages the peer is known to have processed.
+    confirmed: u64,
+}
+
+/// One process's end of total-order multicast within a fixed group.
+#[derive(Clone, Debug)]
+pub struct TotalOrderMulticast<P, T, C = ScalarLamportClock<P>> {
+    pid: P,
+    clock: C,
+    peers: HashMap<P, Peer<C, T>>,
+    /// The messages sent that some peer isn't known to have processed, oldest first.
+    sent: Vec<(u64, C, Kind<T>)>,
+    /// The number of messages sent so far.
+    seq: u64,
+    /// Messages received or multicast, but not delivered yet, in delivery order.
+    held_back: BTreeMap<(C, P), T>,
+}
+
+impl<P, T> TotalOrderMulticast<P, T>
+where
+    P: Ord + Hash + Clone,
+    T: Clone,
+{
+    /// Constructs process `pid`'s end of multicast within the group of the given processes
+    /// (which `pid` is taken to be one of), timestamping messages with a [`ScalarLamportClock`].
+    pub fn new(pid: P, group: impl IntoIterator<Item = P>) -> Self {
+        Self::with_clock(pid.clone(), ScalarLamportClock::new(pid), group)
+    }
+}
+
+impl<P, T, C> TotalOrderMulticast<P, T, C>
+where
+    P: Ord + Hash + Clone,
+    T: Clone,
+    C: LamportClock + Ord + Clone,
+{
+    /// Constructs process `pid`'s end of multicast within the group of the given processes,
+    /// timestamping messages with the given clock. The clock's order has to be total, and
+    /// consistent with causality.
+    pub fn with_clock(pid: P, clock: C, group: impl IntoIterator<Item = P>) -> Self {
+        let peers = group
+            .into_iter()
+            .filter(|k| *k != pid)
+            .map(|k| {
+                let peer = Peer {
+                    processed: 0,
+                    early: BTreeMap::new(),
+                    latest: None,
+                    confirmed: 0,
+                };
+                (k, peer)
+            })
+            .collect();
+        Self {
+            pid,
+            clock,
+            peers,
+            sent: Vec::new(),
+            seq: 0,
+            held_back: BTreeMap::new(),
+        }
+    }
+
+    /// Multicasts a payload to the group, and returns the messages to send for it.
+    pub fn broadcast(&mut self, payload: T) -> Outgoing<P, C, T> {
+        let timestamp = self.clock.send();
+        self.held_back
+            .insert((timestamp.clone(), self.pid.clone()), payload.clone());
+        self.send(timestamp, Kind::Data(payload))
+    }
+
+    /// Handles a message received from the transport, and returns the messages to send in
+    /// response. Messages from processes outside the group are ignored, and duplicates only get
+    /// a receipt.
+    pub fn on_message(&mut self, envelope: Envelope<P, C, T>) -> Outgoing<P, C, T> {
+        let Some(peer) = self.peers.get_mut(&envelope.sender) else {
+            return Vec::new();
+        };
+        peer.confirmed = peer.confirmed.max(envelope.received);
+        if let Kind::Receipt = envelope.kind {
+            self.forget_confirmed();
+            return Vec::new();
+        }
+        let duplicate = envelope.seq <= peer.processed;
+        if !duplicate {
+            peer.early
+                .entry(envelope.seq)
+                .or_insert((envelope.timestamp, envelope.kind));
+        }
+
+        let (mut acknowledge, mut processed) = (false, false);
+        while let Some((timestamp, kind)) = peer.early.remove(&(peer.processed + 1)) {
+            peer.processed += 1;
+            processed = true;
+            self.clock.receive(&timestamp);
+            if let Kind::Data(payload) = kind {
+                self.held_back
+                    .insert((timestamp.clone(), envelope.sender.clone()), payload);
+                acknowledge = true;
+            }
+            peer.latest = Some(timestamp);
+        }
+
+        self.forget_confirmed();
+        if !acknowledge {
+            if !duplicate && !processed {
+                return Vec::new();
+            }
+            // Receipts aren't ordered, so the clock as it stands does for their timestamp.
+            let receipt = self.envelope(&envelope.sender, 0, &self.clock, &Kind::Receipt);
+            return vec![(envelope.sender, receipt)];
+        }
+        // A single acknowledgement, timestamped after everything processed, covers them all.
+        let timestamp = self.clock.send();
+        self.send(timestamp, Kind::Ack)
+    }
+
+    /// Removes and returns the messages that can be delivered, in the group's total order, with
+    /// their senders.
+    pub fn deliverable(&mut self) -> Vec<(P, T)> {
+        let mut deliverable = Vec::new();
+        while let Some(entry) = self.held_back.first_entry() {
+            let (timestamp, sender) = entry.key();
+            // Every other process has to have sent something after the message, so that nothing
+            // before it can still be on its way. Anything its sender sent before it was processed
+            // before it, so the message itself does for the sender.
+            let stable = self.peers.iter().all(|(k, peer)| {
+                peer.latest
+                    .as_ref()
+                    .is_some_and(|latest| (latest, k) >= (timestamp, sender))
+            });
+            if !stable {
+                break;
+            }
+            let ((_, sender), payload) = entry.remove_entry();
+            deliverable.push((sender, payload));
+        }
+        deliverable
+    }
+
+    /// Returns the messages sent that each peer isn't known to have received yet, to be sent
+    /// again.
+    pub fn retransmit(&self) -> Outgoing<P, C, T> {
+        let mut outgoing = Vec::new();
+        for (k, peer) in &self.peers {
+            for (seq, timestamp, kind) in &self.sent {
+                if *seq > peer.confirmed {
+                    outgoing.push((k.clone(), self.envelope(k, *seq, timestamp, kind)));
+                }
+            }
+        }
+        outgoing
+    }
+
+    /// The number of messages received or multicast that haven't been delivered yet.
+    pub fn held_back(&self) -> usize {
+        self.held_back.len()
+    }
+
+    fn send(&mut self, timestamp: C, kind: Kind<T>) -> Outgoing<P, C, T> {
+        self.seq += 1;
+        let outgoing = self
+            .peers
+            .keys()
+            .map(|k| (k.clone(), self.envelope(k, self.seq, &timestamp, &kind)))
+            .collect();
+        self.sent.push((self.seq, timestamp, kind));
+        outgoing
+    }
+
+    fn envelope(&self, to: &P, seq: u64, timestamp: &C, kind: &Kind<T>) -> Envelope<P, C, T> {
+        Envelope {
+            sender: self.pid.clone(),
+            seq,
+            received: self.peers[to].processed,
+            timestamp: timestamp.clone(),
+            kind: kind.clone(),
+        }
+    }
+
+    /// Drops the sent messages that every peer has processed.
+    fn forget_confirmed(&mut self) {
+        let confirmed = self
+            .peers
+            .values()
+            .map(|peer| peer.confirmed)
+            .min()
+            .unwrap_or(self.seq);
+        self.sent.retain(|(seq, _, _)| *seq > confirmed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Rng;
+
+    /// Simulates processes that multicast over a network that drops, duplicates and reorders
+    /// messages, retransmitting whenever it runs dry.
+    fn simulate(seed: u64, processes: usize, broadcasts: usize) {
+        let mut rng = Rng::new(seed);
+        let group: Vec<usize> = (0..processes).collect();
+        let mut members: Vec<_> = group
+            .iter()
+            .map(|&i| TotalOrderMulticast::<usize, (usize, usize)>::new(i, group.clone()))
+            .collect();
+        let mut logs = vec![Vec::new(); processes];
+        let mut network = Vec::new();
+        let mut sent = 0;
+
+        while logs.iter().any(|log| log.len() < broadcasts) {
+            if sent < broadcasts && rng.below(4) == 0 {
+                let sender = rng.below(processes);
+                network.extend(members[sender].broadcast((sender, sent)));
+                sent += 1;
+            } else if network.is_empty() {
+                for member in &members {
+                    network.extend(member.retransmit());
+                }
+            } else {
+                let (to, envelope) = rng.take(&mut network);
+                match rng.below(10) {
+                    0 => continue,
+                    1 => network.push((to, envelope.clone())),
+                    _ => {}
+                }
+                network.extend(members[to].on_message(envelope));
+            }
+            for (member, log) in members.iter_mut().zip(&mut logs) {
+                log.extend(member.deliverable());
+            }
+        }
+
+        // Once the group goes quiet, everything sent ends up confirmed, and retransmission stops.
+        for _ in 0..10_000 {
+            if network.is_empty() {
+                network.extend(members.iter().flat_map(|member| member.retransmit()));
+                if network.is_empty() {
+                    break;
+                }
+            }
+            let (to, envelope) = rng.take(&mut network);
+            if rng.below(10) != 0 {
+                network.extend(members[to].on_message(envelope));
+            }
+        }
+        assert!(network.is_empty(), "seed {seed}: still retransmitting");
+
+        for (member, log) in members.iter().zip(&logs) {
+            assert_eq!(member.held_back(), 0, "seed {seed}");
+            assert_eq!(log, &logs[0], "seed {seed}");
+        }
+        // Every process delivers its own messages in the order it multicast them.
+        for sender in 0..processes {
+            let own: Vec<_> = logs[0].iter().filter(|(k, _)| *k == sender).collect();
+            assert!(own.is_sorted_by_key(|(_, (_, n))| n), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_lossy_network() {
+        for seed in 1..=100 {
+            simulate(seed, 4, 20);
+        }
+    }
+
+    /// Picks out the message addressed to `to`.
+    fn to<C: Clone, T: Clone>(
+        outgoing: &Outgoing<&'static str, C, T>,
+        to: &str,
+    ) -> Envelope<&'static str, C, T> {
+        let (_, envelope) = outgoing.iter().find(|(k, _)| *k == to).unwrap();
+        envelope.clone()
+    }
+
+    #[test]
+    fn test_hold_back() {
+        let group = ["a", "b", "c"];
+        let [mut a, mut b, mut c] = group.map(|i| TotalOrderMulticast::new(i, group));
+
+        // Concurrent messages, timestamped (1, a) and (1, c).
+        let x = a.broadcast("x");
+        let y = c.broadcast("y");
+        b.on_message(to(&x, "b"));
+        assert!(b.deliverable().is_empty());
+        b.on_message(to(&y, "b"));
+        // Nothing after x has been heard from a yet, which might still multicast something
+        // timestamped before y.
+        assert_eq!(b.deliverable(), [("a", "x")]);
+        assert_eq!(b.held_back(), 1);
+
+        let ack = a.on_message(to(&y, "a"));
+        // A duplicate only gets a receipt.
+        let receipt = to(&b.on_message(to(&x, "b")), "a");
+        assert_eq!((receipt.seq, receipt.received), (0, 1));
+        assert_eq!(receipt.kind, Kind::Receipt);
+        b.on_message(to(&ack, "b"));
+        assert_eq!(b.deliverable(), [("c", "y")]);
+        c.on_message(to(&x, "c"));
+
+        // a doesn't know yet that anyone got x or its ack.
+        assert_eq!(a.retransmit().len(), 4);
+        for (k, envelope) in b.retransmit() {
+            if k == "a" {
+                a.on_message(envelope);
+            }
+        }
+        let retransmit = a.retransmit();
+        assert_eq!(retransmit.len(), 2);
+        assert!(retransmit.iter().all(|(k, _)| *k == "c"));
+    }
+
+    #[test]
+    fn test_single_broadcast() {
+        // The sender doesn't send anything after its last message, which is delivered anyway.
+        let group = ["a", "b", "c"];
+        let mut members = group.map(|i| TotalOrderMulticast::new(i, group));
+        let mut network = members[0].broadcast("x");
+        while let Some((to, envelope)) = network.pop() {
+            let i = group.iter().position(|k| *k == to).unwrap();
+            network.extend(members[i].on_message(envelope));
+        }
+        for member in &mut members {
+            assert_eq!(member.deliverable(), [("a", "x")]);
+            // The acknowledgements got receipts, so nothing is left to retransmit.
+            assert!(member.retransmit().is_empty());
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! Offline analysis of traces whose events are stamped with vector clocks: the consistent cuts of
+//! a run, and whether a predicate over the global state possibly or definitely held during it.
+//!
+//! A cut takes a prefix of every process's events, and is consistent if it's closed under
+//! happens-before, i.e. it could have been the global state at some point of the run as seen by an
+//! outside observer. The consistent cuts form a lattice: every run of the system, i.e. every order
+//! the events could have been observed in, is a path through it from the empty cut to the full
+//! one, adding one event at a time.
+//!
+//! A predicate *possibly* held if some consistent cut satisfies it, and *definitely* held if every
+//! path through the lattice passes through a cut that does. Both are decided by walking the lattice
+//! level by level, each level holding the cuts with the same number of events.
+//!
+//! See "Consistent Detection of Global Predicates" by Cooper and Marzullo, and "Elements of
+//! Distributed Computing" by Garg.
+
+use crate::vector_clock::VectorClock;
+use num_traits::CheckedAdd;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+
+/// The error returned when two events of a process carry the same clock entry of the process, so
+/// that neither can be told to come first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateEvent<K>(pub K);
+
+impl<K> fmt::Display for DuplicateEvent<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "two events of the same process have the same clock entry"
+        )
+    }
+}
+
+impl<K: fmt::Debug> std::error::Error for DuplicateEvent<K> {}
+
+#[derive(Clone, Debug)]
+struct Event<E> {
+    payload: E,
+    /// For each process, by index, the number of its events this one happens after (or is).
+    after: Vec<usize>,
+}
+
+/// The events of a run, by process, in the order each process executed them.
+#[derive(Clone, Debug)]
+pub struct Trace<K, E> {
+    processes: Vec<K>,
+    events: Vec<Vec<Event<E>>>,
+}
+
+impl<K, E> Trace<K, E>
+where
+    K: Ord + Hash + Clone,
+{
+    /// Builds the trace of the given events, each with the clock it was stamped with (by the
+    /// process owning the clock) and e.g. the state of the process after it. Processes don't need
+    /// to trace all of their events, nor the events to be in any particular order.
+    pub fn new<V>(
+        events: impl IntoIterator<Item = (VectorClock<K, V>, E)>,
+    ) -> Result<Self, DuplicateEvent<K>>
+    where
+        V: CheckedAdd + From<u8> + Ord + Default + Clone,
+    {
+        let by_process = by_process(events, |(clock, _)| (clock.owner(), clock))?;
+        // For each process, the entries of its own events in its clocks, in order.
+        let own: Vec<Vec<V>> = by_process
+            .iter()
+            .map(|(k, events)| events.iter().map(|(clock, _)| clock.get(k)).collect())
+            .collect();
+
+        let processes: Vec<K> = by_process.iter().map(|(k, _)| k.clone()).collect();
+        let events = by_process
+            .into_iter()
+            .map(|(_, events)| {
+                let events = events.into_iter().map(|(clock, payload)| {
+                    let after = processes
+                        .iter()
+                        .zip(&own)
+                        .map(|(j, entries)| {
+                            let seen = clock.get(j);
+                            entries.partition_point(|entry| *entry <= seen)
+                        })
+                        .collect();
+                    Event { payload, after }
+                });
+                events.collect()
+            })
+            .collect();
+        Ok(Self { processes, events })
+    }
+
+    /// Returns the processes that have events in the trace, in order.
+    pub fn processes(&self) -> &[K] {
+        &self.processes
+    }
+
+    /// Returns the number of events in the trace.
+    pub fn len(&self) -> usize {
+        self.events.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the consistent cuts of the run, level by level from the empty cut to the full one.
+    /// Only two levels of the lattice are held at a time.
+    pub fn cuts(&self) -> Cuts<'_, K, E> {
+        Cuts {
+            trace: self,
+            level: vec![vec![0; self.processes.len()]],
+            next: HashSet::new(),
+        }
+    }
+
+    /// Returns a consistent cut that satisfies the predicate, if the predicate possibly held.
+    pub fn possibly(
+        &self,
+        mut predicate: impl FnMut(&Cut<'_, K, E>) -> bool,
+    ) -> Option<Cut<'_, K, E>> {
+        self.cuts().find(|cut| predicate(cut))
+    }
+
+    /// Returns whether the predicate definitely held, i.e. whether every observation of the run
+    /// passes through a consistent cut satisfying it.
+    pub fn definitely(&self, mut predicate: impl FnMut(&Cut<'_, K, E>) -> bool) -> bool {
+        let full: Vec<usize> = self.events.iter().map(Vec::len).collect();
+        // The cuts of the current level that can be reached without satisfying the predicate.
+        let mut level = vec![vec![0; self.processes.len()]];
+        loop {
+            level.retain(|counts| {
+                let cut = Cut {
+                    trace: self,
+                    counts: counts.clone(),
+                };
+                !predicate(&cut)
+            });
+            if level.is_empty() {
+                return true;
+            }
+            if level.contains(&full) {
+                return false;
+            }
+            let next: HashSet<Vec<usize>> = level
+                .iter()
+                .flat_map(|counts| self.successors(counts))
+                .collect();
+            level = next.into_iter().collect();
+        }
+    }
+
+    /// The consistent cuts that extend the given one by a single event.
+    fn successors<'a>(&'a self, counts: &'a [usize]) -> impl Iterator<Item = Vec<usize>> + 'a {
+        self.events
+            .iter()
+            .enumerate()
+            .filter_map(move |(k, events)| {
+                let event = events.get(counts[k])?;
+                let consistent = event
+                    .after
+                    .iter()
+                    .zip(counts)
+                    .enumerate()
+                    .all(|(j, (after, count))| j == k || after <= count);
+                consistent.then(|| {
+                    let mut counts = counts.to_vec();
+                    counts[k] += 1;
+                    counts
+                })
+            })
+    }
+}
+
+/// Groups items by the process their event happened on, as returned by `stamp` along with the
+/// event's clock, in order of process. Each process's items are sorted by their clocks' entries of
+/// the process, which mustn't repeat. Shared by [`Trace::new`] and the exporters.
+pub(crate) fn by_process<K, V, T>(
+    items: impl IntoIterator<Item = T>,
+    stamp: impl Fn(&T) -> (&K, &VectorClock<K, V>),
+) -> Result<Vec<(K, Vec<T>)>, DuplicateEvent<K>>
+where
+    K: Ord + Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+    let mut groups: HashMap<K, Vec<T>> = HashMap::new();
+    for item in items {
+        groups.entry(stamp(&item).0.clone()).or_default().push(item);
+    }
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (k, items) in &mut groups {
+        items.sort_by_key(|item| stamp(item).1.get(k));
+        let own = |item: &T| stamp(item).1.get(k);
+        if items.windows(2).any(|pair| own(&pair[0]) == own(&pair[1])) {
+            return Err(DuplicateEvent(k.clone()));
+        }
+    }
+    Ok(groups)
+}
+
+/// A consistent cut of a run: a prefix of every process's events, closed under happens-before.
+#[derive(Clone, Debug)]
+pub struct Cut<'a, K, E> {
+    trace: &'a Trace<K, E>,
+    /// For each process, by index, the number of its events in the cut.
+    counts: Vec<usize>,
+}
+
+impl<K: Ord + Hash + Clone, E> Cut<'_, K, E> {
+    /// Returns the latest event of process `k` in the cut, if it has any.
+    pub fn latest(&self, k: &K) -> Option<&E> {
+        let k = self.trace.processes.binary_search(k).ok()?;
+        let count = self.counts[k].checked_sub(1)?;
+        Some(&self.trace.events[k][count].payload)
+    }
+
+    /// Returns the number of events of each process in the cut.
+    pub fn counts(&self) -> impl Iterator<Item = (&K, usize)> {
+        self.trace.processes.iter().zip(self.counts.iter().copied())
+    }
+}
+
+/// An iterator over the consistent cuts of a run, level by level.
+pub struct Cuts<'a, K, E> {
+    trace: &'a Trace<K, E>,
+    /// The cuts of the current level yet to be returned, in reverse order.
+    level: Vec<Vec<usize>>,
+    /// The cuts of the next level found so far.
+    next: HashSet<Vec<usize>>,
+}
+
+impl<'a, K: Ord + Hash + Clone, E> Iterator for Cuts<'a, K, E> {
+    type Item = Cut<'a, K, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.level.is_empty() {
+            self.level = self.next.drain().collect();
+            self.level.sort_unstable_by(|a, b| b.cmp(a));
+        }
+        let counts = self.level.pop()?;
+        self.next.extend(self.trace.successors(&counts));
+        Some(Cut {
+            trace: self.trace,
+            counts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two processes that each set a variable and reset it again, optionally telling each other
+    /// that they set it, and waiting for the other's message before resetting it.
+    fn run(messages: bool) -> Trace<usize, i32> {
+        let [mut p, mut q] = [0, 1].map(VectorClock::<usize, usize>::new);
+        let p1 = p.send();
+        let q1 = q.send();
+        if messages {
+            p.receive(&q1);
+            q.receive(&p1);
+        } else {
+            p.bump();
+            q.bump();
+        }
+        Trace::new([(p1, 1), (q1, 1), (p.clone(), 0), (q.clone(), 0)]).unwrap()
+    }
+
+    fn both_set(cut: &Cut<'_, usize, i32>) -> bool {
+        cut.latest(&0) == Some(&1) && cut.latest(&1) == Some(&1)
+    }
+
+    #[test]
+    fn test_possibly_and_definitely() {
+        // Without messages, every interleaving of the four events is possible.
+        let trace = run(false);
+        assert_eq!(trace.len(), 4);
+        assert_eq!(trace.cuts().count(), 9);
+        let witness = trace.possibly(both_set).unwrap();
+        assert_eq!(witness.counts().collect::<Vec<_>>(), [(&0, 1), (&1, 1)]);
+        assert!(!trace.definitely(both_set));
+
+        // Neither process can reset its variable before the other has set its own.
+        let trace = run(true);
+        assert_eq!(trace.cuts().count(), 7);
+        assert!(trace.possibly(both_set).is_some());
+        assert!(trace.definitely(both_set));
+        // Something that held in the initial state definitely held.
+        assert!(trace.definitely(|cut| cut.latest(&0).is_none()));
+        assert!(trace.possibly(|cut| cut.latest(&2).is_some()).is_none());
+    }
+
+    #[test]
+    fn test_cuts() {
+        // A chain of messages 0 -> 1 -> 2 leaves a single path through the lattice.
+        let [mut p0, mut p1, mut p2] = [0, 1, 2].map(VectorClock::<usize, usize>::new);
+        let m0 = p0.send();
+        p1.receive(&m0);
+        let m1 = p1.send();
+        p2.receive(&m1);
+        let trace =
+            Trace::new([(p2.clone(), "received"), (m1, "sent"), (m0.clone(), "sent")]).unwrap();
+        // p1's receive isn't traced, so its send only needs p0's send.
+        let cuts: Vec<Vec<usize>> = trace
+            .cuts()
+            .map(|cut| cut.counts().map(|(_, n)| n).collect())
+            .collect();
+        assert_eq!(cuts, [[0, 0, 0], [1, 0, 0], [1, 1, 0], [1, 1, 1]]);
+
+        assert_eq!(
+            Trace::new([(m0.clone(), ()), (m0, ())]).unwrap_err(),
+            DuplicateEvent(0)
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Clocks across a system are only ever synchronized up to some maximum offset, so a value that a
+//! transaction reads may carry a timestamp that is *later* than the transaction's read timestamp,
+//! despite having been written before the transaction started. Within the window
+//! `(read_timestamp, read_timestamp + max_offset]`, there's no telling which, and the transaction
+//! must be restarted at a timestamp above the value's to avoid missing a write that (in real time)
+//! preceded it. Beyond the window, the value was certainly written later and can be ignored.
+//!
+//! Symmetrically, a transaction that commits at timestamp `t` can only be sure that every clock in
+//! the system has moved past `t` once `max_offset` has elapsed on its own clock; waiting that out
+//! before acknowledging the commit (commit-wait) guarantees that anything that starts afterwards,
+//! anywhere, reads at a timestamp above `t`.
+//!
+//! See "Spanner: Google's Globally-Distributed Database" by Corbett et al., and CockroachDB's
+//! "Living Without Atomic Clocks" for how these interact with multi-version concurrency control.
+
+use crate::LamportClock;
+use crate::hybrid_logical_clock::{HlcTimestamp, HybridLogicalClock, Micros, Resolution};
+use std::time::{Duration, Instant};
+
+pub struct Transaction<R: Resolution = Micros> {
+    /// The timestamp at which the transaction reads. Values with timestamps at or below it are
+    /// visible to the transaction.
+    read_timestamp: HlcTimestamp<R>,
+    /// The highest timestamp a value may have and still possibly have been written before the
+    /// transaction started. Unlike the read timestamp, it never moves on restarts, which bounds
+    /// how many times a transaction can be restarted.
+    uncertainty_limit: HlcTimestamp<R>,
+}
+
+impl<R: Resolution> Transaction<R> {
+    /// Starts a transaction at the clock's current time, taking the uncertainty window from the
+    /// clock's configured maximum offset.
+    pub fn begin(clock: &mut HybridLogicalClock<R>) -> Self {
+        clock.bump();
+        Self::new(clock.timestamp(), clock.max_offset())
+    }
+
+    /// Starts a transaction reading at the given timestamp, with an uncertainty window of
+    /// `max_offset`.
+    pub fn new(read_timestamp: HlcTimestamp<R>, max_offset: Duration) -> Self {
+        let uncertainty_limit = HlcTimestamp::new(
+            read_timestamp.l() + HlcTimestamp::<R>::ticks(max_offset),
+            R::MAX_COUNTER,
+        );
+        Self {
+            read_timestamp,
+            uncertainty_limit,
+        }
+    }
+
+    pub fn read_timestamp(&self) -> HlcTimestamp<R> {
+        self.read_timestamp
+    }
+
+    pub fn uncertainty_limit(&self) -> HlcTimestamp<R> {
+        self.uncertainty_limit
+    }
+
+    /// Observes a value with the given timestamp. If the value falls within the transaction's
+    /// uncertainty window, the transaction must restart: its read timestamp is moved just past the
+    /// value's, and the new read timestamp is returned. Otherwise, the value is either visible or
+    /// certainly in the future, and `None` is returned.
+    pub fn observe(&mut self, value_timestamp: HlcTimestamp<R>) -> Option<HlcTimestamp<R>> {
+        if value_timestamp <= self.read_timestamp || value_timestamp > self.uncertainty_limit {
+            return None;
+        }
+        self.read_timestamp = value_timestamp.successor();
+        Some(self.read_timestamp)
+    }
+
+    /// Returns whether a value with the given timestamp is visible at the read timestamp.
+    pub fn is_visible(&self, value_timestamp: HlcTimestamp<R>) -> bool {
+        value_timestamp <= self.read_timestamp
+    }
+}
+
+/// Returns how much longer one must wait, with the clock's physical time currently at `now`,
+/// before `commit_timestamp` is in the past on every clock in the system (assuming none is more
+/// than `max_offset` ahead of any other).
+pub fn commit_wait_duration<R: Resolution>(
+    commit_timestamp: HlcTimestamp<R>,
+    now: u64,
+    max_offset: Duration,
+) -> Duration {
+    let safe = commit_timestamp.l() + HlcTimestamp::<R>::ticks(max_offset);
+    // The commit timestamp is only in the past once the clock has moved on to the next tick.
+    let ticks = (safe + 1).saturating_sub(now);
+    HlcTimestamp::<R>::new(ticks, 0).as_duration()
+}
+
+/// Blocks until `commit_timestamp` is in the past on every clock in the system (see
+/// [`commit_wait_duration`]), returning how long that took.
+pub fn commit_wait<R: Resolution>(
+    clock: &mut HybridLogicalClock<R>,
+    commit_timestamp: HlcTimestamp<R>,
+) -> Duration {
+    let start = Instant::now();
+    loop {
+        let remaining =
+            commit_wait_duration(commit_timestamp, clock.physical_now(), clock.max_offset());
+        if remaining.is_zero() {
+            return start.elapsed();
+        }
+        std::thread::sleep(remaining);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hybrid_logical_clock::{Millis, SystemClock};
+
+    #[test]
+    fn test_uncertainty_restarts() {
+        let read = HlcTimestamp::<Millis>::new(1_000, 3);
+        let mut txn = Transaction::new(read, Duration::from_millis(250));
+        assert_eq!(txn.uncertainty_limit(), HlcTimestamp::new(1_250, u16::MAX));
+
+        // Values at or below the read timestamp are visible, and those beyond the uncertainty
+        // window are certainly from the future.
+        assert_eq!(txn.observe(HlcTimestamp::new(1_000, 3)), None);
+        assert_eq!(txn.observe(HlcTimestamp::new(999, 9)), None);
+        assert_eq!(txn.observe(HlcTimestamp::new(1_251, 0)), None);
+        assert!(txn.is_visible(HlcTimestamp::new(1_000, 3)));
+
+        // Anything in between forces a restart just past it...
+        assert_eq!(
+            txn.observe(HlcTimestamp::new(1_100, 7)),
+            Some(HlcTimestamp::new(1_100, 8))
+        );
+        assert!(txn.is_visible(HlcTimestamp::new(1_100, 7)));
+        // ...which doesn't move the uncertainty limit.
+        assert_eq!(
+            txn.observe(HlcTimestamp::new(1_250, u16::MAX)),
+            Some(HlcTimestamp::new(1_251, 0))
+        );
+        assert_eq!(txn.uncertainty_limit(), HlcTimestamp::new(1_250, u16::MAX));
+        assert_eq!(txn.observe(HlcTimestamp::new(1_250, 0)), None);
+    }
+
+    #[test]
+    fn test_commit_wait() {
+        let max_offset = Duration::from_millis(20);
+        let commit = HlcTimestamp::<Millis>::new(1_000, 5);
+        assert_eq!(
+            commit_wait_duration(commit, 1_000, max_offset),
+            Duration::from_millis(21)
+        );
+        assert_eq!(
+            commit_wait_duration(commit, 1_021, max_offset),
+            Duration::ZERO
+        );
+
+        let mut clock = HybridLogicalClock::<Millis>::with_resolution()
+            .with_time_source(SystemClock)
+            .with_max_offset(max_offset);
+        let txn = Transaction::begin(&mut clock);
+        let waited = commit_wait(&mut clock, txn.read_timestamp());
+        assert!(waited >= max_offset);
+        assert!(clock.physical_now() > txn.uncertainty_limit().l());
+    }
+}
@@ -0,0 +1,627 @@
+//! A centralized timestamp oracle (TSO), as in Percolator or TiDB's placement driver, which hands
+//! out strictly increasing batches of packed HLC timestamps to clients over a local TCP or Unix
+//! socket.
+//!
+//! The oracle never hands out a timestamp above its *lease*, a high-water mark that is persisted
+//! to disk before any timestamp below it is issued. A restarted oracle resumes above the persisted
+//! lease, so it never goes backwards, even if its physical clock does.
+//!
+//! The wire protocol is line-based: a client sends `GET <count>`, to which the oracle replies with
+//! either `OK <first> <count>`, granting the timestamps `first..first + count`, or `ERR <reason>`.
+
+use crate::LamportClock;
+use crate::hybrid_logical_clock::{HlcTimestamp, HybridLogicalClock, Micros};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+/// The largest batch a client may ask for at once.
+const MAX_BATCH: u64 = 1 << 16;
+
+/// Hands out timestamps, persisting its lease to `lease_path`.
+pub struct Oracle {
+    clock: HybridLogicalClock<Micros>,
+    /// The last timestamp handed out.
+    last: u64,
+    /// The persisted high-water mark, which no timestamp handed out may exceed.
+    lease: u64,
+    /// How far beyond the timestamps it needs the oracle extends its lease, in packed units.
+    lease_window: u64,
+    lease_path: PathBuf,
+}
+
+impl Oracle {
+    /// Opens an oracle, resuming above the lease persisted at `lease_path` if there is one. The
+    /// lease is extended `lease_window` at a time, trading disk writes for the size of the jump
+    /// in timestamps a restart causes.
+    pub fn open(
+        clock: HybridLogicalClock<Micros>,
+        lease_path: impl Into<PathBuf>,
+        lease_window: Duration,
+    ) -> io::Result<Self> {
+        let lease_path = lease_path.into();
+        let lease = match fs::read_to_string(&lease_path) {
+            Ok(contents) => contents
+                .trim()
+                .parse()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "corrupt lease file"))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        let lease_window =
+            HlcTimestamp::<Micros>::new(HlcTimestamp::<Micros>::ticks(lease_window), 0)
+                .pack()
+                .max(1);
+        Ok(Self {
+            clock,
+            // Anything up to the lease may have been handed out before a restart.
+            last: lease,
+            lease,
+            lease_window,
+            lease_path,
+        })
+    }
+
+    /// Allocates `count` consecutive timestamps, all greater than any handed out before.
+    ///
+    /// This reads the oracle's clock, which blocks whenever the clock synchronizes with its
+    /// reference (e.g. an SNTP query, for [`HybridLogicalClock::new`]). A server holds the oracle
+    /// locked meanwhile, stalling every client, so prefer a time source that doesn't block.
+    pub fn allocate(&mut self, count: u64) -> io::Result<Range<u64>> {
+        if count == 0 || count > MAX_BATCH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("batch size must be between 1 and {MAX_BATCH}"),
+            ));
+        }
+        self.clock.bump();
+        let first = u64::max(self.last + 1, self.clock.compact_timestamps());
+        let end = first + count;
+        if end - 1 > self.lease {
+            self.persist_lease(end - 1 + self.lease_window)?;
+        }
+        self.last = end - 1;
+        // Keep the clock ahead of everything handed out, so that it keeps up under heavy load.
+        self.clock
+            .receive(&HybridLogicalClock::decompose_into_timestamps(self.last));
+        Ok(first..end)
+    }
+
+    /// Durably records the new lease before anything below it is handed out.
+    fn persist_lease(&mut self, lease: u64) -> io::Result<()> {
+        let tmp = self.lease_path.with_extension("tmp");
+        let mut file = fs::File::create(&tmp)?;
+        writeln!(file, "{lease}")?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.lease_path)?;
+        // The rename itself only survives a crash once the directory entry is on disk.
+        #[cfg(unix)]
+        {
+            let dir = match self.lease_path.parent() {
+                Some(dir) if dir != Path::new("") => dir,
+                _ => Path::new("."),
+            };
+            fs::File::open(dir)?.sync_all()?;
+        }
+        self.lease = lease;
+        Ok(())
+    }
+}
+
+/// Where an oracle listens: a TCP address (`host:port`) or, on Unix, a socket path
+/// (`unix:<path>`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for Address {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        match s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) => Ok(Self::Unix(PathBuf::from(path))),
+            #[cfg(not(unix))]
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unix sockets are not supported on this platform",
+            )),
+            None => Ok(Self::Tcp(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            #[cfg(unix)]
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(addr: &Address) -> io::Result<Self> {
+        match addr {
+            Address::Tcp(addr) => TcpStream::connect(addr.as_str()).map(Self::Tcp),
+            #[cfg(unix)]
+            Address::Unix(path) => UnixStream::connect(path).map(Self::Unix),
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Self::Tcp(stream) => stream.try_clone().map(Self::Tcp),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.try_clone().map(Self::Unix),
+        }
+    }
+
+    fn shutdown(&self) {
+        // The peer may have hung up already, which is just as good.
+        let _ = match self {
+            Self::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.shutdown(Shutdown::Both),
+        };
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// An oracle bound to an address, ready to serve clients.
+pub struct Server {
+    listener: Listener,
+    address: Address,
+    oracle: Arc<Mutex<Oracle>>,
+    stopped: Arc<AtomicBool>,
+    /// The open connections, by id, so that they can be dropped on shutdown.
+    connections: Arc<Mutex<HashMap<u64, Stream>>>,
+    /// Called with every error accepting a client.
+    on_accept_error: AcceptErrorHandler,
+}
+
+/// What a server does with an error accepting a client, having carried on.
+type AcceptErrorHandler = Box<dyn Fn(&io::Error) + Send + Sync>;
+
+impl Server {
+    /// Binds the oracle to `address`. For TCP, binding port 0 picks a free port, which
+    /// [`Server::address`] then reports. A stale Unix socket left behind by a previous oracle is
+    /// replaced, but anything else at the path is left alone, failing with `AddrInUse`.
+    pub fn bind(address: &Address, oracle: Oracle) -> io::Result<Self> {
+        let (listener, address) = match address {
+            Address::Tcp(addr) => {
+                let listener = TcpListener::bind(addr.as_str())?;
+                let address = Address::Tcp(listener.local_addr()?.to_string());
+                (Listener::Tcp(listener), address)
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                if UnixStream::connect(path).is_err() {
+                    match fs::symlink_metadata(path) {
+                        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+                        Ok(_) => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::AddrInUse,
+                                format!("{} exists and is not a socket", path.display()),
+                            ));
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                (
+                    Listener::Unix(UnixListener::bind(path)?),
+                    Address::Unix(path.clone()),
+                )
+            }
+        };
+        Ok(Self {
+            listener,
+            address,
+            oracle: Arc::new(Mutex::new(oracle)),
+            stopped: Arc::new(AtomicBool::new(false)),
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            on_accept_error: Box::new(|_| {}),
+        })
+    }
+
+    /// Sets what to do with errors accepting a client, e.g. logging them. They are ignored by
+    /// default.
+    pub fn with_accept_error_handler(
+        mut self,
+        handler: impl Fn(&io::Error) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_accept_error = Box::new(handler);
+        self
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Serves clients until the server is shut down, handling each connection on its own thread.
+    /// Failing to accept a client (e.g. for lack of file descriptors) is handed to the accept error
+    /// handler, and the server carries on.
+    pub fn serve(&self) -> io::Result<()> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        loop {
+            let accepted = match &self.listener {
+                Listener::Tcp(listener) => listener.accept().map(|(s, _)| Stream::Tcp(s)),
+                #[cfg(unix)]
+                Listener::Unix(listener) => listener.accept().map(|(s, _)| Stream::Unix(s)),
+            };
+            if self.stopped.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            let (id, stream) = match accepted.and_then(|stream| Ok((stream.try_clone()?, stream))) {
+                Ok((clone, stream)) => {
+                    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+                    self.connections.lock().unwrap().insert(id, clone);
+                    (id, stream)
+                }
+                Err(e) => {
+                    (self.on_accept_error)(&e);
+                    // Give whatever ran out (e.g. file descriptors) a chance to be freed.
+                    std::thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+            };
+            let (oracle, connections) = (Arc::clone(&self.oracle), Arc::clone(&self.connections));
+            std::thread::spawn(move || {
+                let result = handle_connection(stream, &oracle);
+                connections.lock().unwrap().remove(&id);
+                result
+            });
+        }
+    }
+
+    /// Serves clients on a background thread until the returned handle is shut down.
+    pub fn spawn(self) -> ServerHandle {
+        let address = self.address.clone();
+        let stopped = Arc::clone(&self.stopped);
+        let connections = Arc::clone(&self.connections);
+        let thread = std::thread::spawn(move || self.serve());
+        ServerHandle {
+            address,
+            stopped,
+            connections,
+            thread,
+        }
+    }
+}
+
+/// Handle to a server running on a background thread.
+pub struct ServerHandle {
+    address: Address,
+    stopped: Arc<AtomicBool>,
+    connections: Arc<Mutex<HashMap<u64, Stream>>>,
+    thread: JoinHandle<io::Result<()>>,
+}
+
+impl ServerHandle {
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Stops accepting clients, drops every open connection, and waits for the server to exit.
+    pub fn shutdown(self) -> io::Result<()> {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake the listener up, so that it notices it has been stopped.
+        let _ = Stream::connect(&self.address);
+        for (_, connection) in self.connections.lock().unwrap().drain() {
+            connection.shutdown();
+        }
+        self.thread
+            .join()
+            .map_err(|_| io::Error::other("server thread panicked"))?
+    }
+}
+
+fn handle_connection(stream: Stream, oracle: &Mutex<Oracle>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let reply = match line.strip_prefix("GET ").map(str::parse::<u64>) {
+            Some(Ok(count)) => match oracle.lock().unwrap().allocate(count) {
+                Ok(batch) => format!("OK {} {}", batch.start, count),
+                Err(e) => format!("ERR {e}"),
+            },
+            _ => format!("ERR malformed request: {line:?}"),
+        };
+        writeln!(writer, "{reply}")?;
+    }
+    Ok(())
+}
+
+/// A client that fetches timestamps from an oracle in batches, and hands them out one by one.
+///
+/// Should the oracle go away, the client fails over to the next of its addresses (and eventually
+/// back to the first, e.g. once the oracle has restarted). Timestamps handed out by a client are
+/// strictly increasing, across failovers too.
+pub struct Client {
+    addresses: Vec<Address>,
+    /// The index into `addresses` of the oracle currently (or last) connected to.
+    current: usize,
+    connection: Option<(BufReader<Stream>, Stream)>,
+    batch: Range<u64>,
+    batch_size: u64,
+    last: Option<u64>,
+}
+
+impl Client {
+    pub fn new(addresses: Vec<Address>, batch_size: u64) -> Self {
+        assert!(!addresses.is_empty(), "a client needs an oracle to talk to");
+        assert!((1..=MAX_BATCH).contains(&batch_size));
+        Self {
+            addresses,
+            current: 0,
+            connection: None,
+            batch: 0..0,
+            batch_size,
+            last: None,
+        }
+    }
+
+    /// Returns the next timestamp, fetching a new batch from the oracle if the cached one has
+    /// run out.
+    pub fn next_timestamp(&mut self) -> io::Result<u64> {
+        if self.batch.is_empty() {
+            self.batch = self.fetch()?;
+        }
+        let timestamp = self.batch.start;
+        self.batch.start += 1;
+        self.last = Some(timestamp);
+        Ok(timestamp)
+    }
+
+    /// Fetches a batch, trying every address once before giving up.
+    fn fetch(&mut self) -> io::Result<Range<u64>> {
+        let mut error = None;
+        // A connection that has gone stale gets one more (fresh) attempt at the same address.
+        for _ in 0..=self.addresses.len() {
+            let fresh = self.connection.is_none();
+            match self.request() {
+                Ok(batch) => {
+                    if self.last.is_some_and(|last| batch.start <= last) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "oracle went backwards",
+                        ));
+                    }
+                    return Ok(batch);
+                }
+                Err(e) => {
+                    self.connection = None;
+                    if fresh {
+                        self.current = (self.current + 1) % self.addresses.len();
+                    }
+                    error = Some(e);
+                }
+            }
+        }
+        Err(error.unwrap())
+    }
+
+    fn request(&mut self) -> io::Result<Range<u64>> {
+        if self.connection.is_none() {
+            let stream = Stream::connect(&self.addresses[self.current])?;
+            self.connection = Some((BufReader::new(stream.try_clone()?), stream));
+        }
+        let (reader, writer) = self.connection.as_mut().unwrap();
+        writeln!(writer, "GET {}", self.batch_size)?;
+
+        let mut reply = String::new();
+        if reader.read_line(&mut reply)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let malformed = || io::Error::new(io::ErrorKind::InvalidData, "malformed reply");
+        let mut words = reply.split_whitespace();
+        match words.next() {
+            Some("OK") => {
+                let mut number = || words.next().and_then(|w| w.parse::<u64>().ok());
+                let (first, count) = (
+                    number().ok_or_else(malformed)?,
+                    number().ok_or_else(malformed)?,
+                );
+                Ok(first..first + count)
+            }
+            Some("ERR") => Err(io::Error::other(reply[3..].trim().to_string())),
+            _ => Err(malformed()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hybrid_logical_clock::{ReferenceTime, SystemClock, TimeSource};
+
+    /// A time source stuck in 1970, to make sure oracles don't rely on their clocks to never go
+    /// backwards.
+    struct Epoch;
+
+    impl TimeSource for Epoch {
+        fn system_time(&mut self) -> Duration {
+            Duration::ZERO
+        }
+
+        fn reference_time(&mut self) -> Option<ReferenceTime> {
+            None
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("clock-tso-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn failover(address: Address, dir: &Path) {
+        let lease_path = dir.join("tso.lease");
+        let lease_window = Duration::from_millis(100);
+
+        let clock = HybridLogicalClock::new().with_time_source(SystemClock);
+        let oracle = Oracle::open(clock, &lease_path, lease_window).unwrap();
+        let server = Server::bind(&address, oracle).unwrap().spawn();
+        let address = server.address().clone();
+
+        let mut client = Client::new(vec![address.clone()], 10);
+        let mut issued = Vec::new();
+        for _ in 0..25 {
+            issued.push(client.next_timestamp().unwrap());
+        }
+        server.shutdown().unwrap();
+
+        // The restarted oracle's clock is decades behind, yet it resumes past everything that
+        // was handed out before.
+        let clock = HybridLogicalClock::new().with_time_source(Epoch);
+        let oracle = Oracle::open(clock, &lease_path, lease_window).unwrap();
+        let server = Server::bind(&address, oracle).unwrap().spawn();
+        for _ in 0..25 {
+            issued.push(client.next_timestamp().unwrap());
+        }
+        let mut other = Client::new(vec![address], 3);
+        issued.push(other.next_timestamp().unwrap());
+        server.shutdown().unwrap();
+
+        assert!(issued.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_tcp_failover() {
+        let dir = temp_dir("tcp");
+        // Bind an arbitrary free port, which the restarted oracle then binds again.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        failover(Address::Tcp(format!("127.0.0.1:{port}")), &dir);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_failover() {
+        let dir = temp_dir("unix");
+        failover(Address::Unix(dir.join("tso.sock")), &dir);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bind_keeps_other_files() {
+        let dir = temp_dir("bind");
+        let path = dir.join("precious");
+        fs::write(&path, "data").unwrap();
+        let clock = HybridLogicalClock::new().with_time_source(SystemClock);
+        let oracle = Oracle::open(clock, dir.join("tso.lease"), Duration::from_secs(1)).unwrap();
+        let error = Server::bind(&Address::Unix(path.clone()), oracle)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "data");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_closed_connections_are_dropped() {
+        let dir = temp_dir("connections");
+        let clock = HybridLogicalClock::new().with_time_source(SystemClock);
+        let oracle = Oracle::open(clock, dir.join("tso.lease"), Duration::from_secs(1)).unwrap();
+        let server = Server::bind(&Address::Tcp("127.0.0.1:0".into()), oracle)
+            .unwrap()
+            .spawn();
+
+        for _ in 0..3 {
+            let mut client = Client::new(vec![server.address().clone()], 1);
+            client.next_timestamp().unwrap();
+        }
+        // The connections are dropped as soon as their clients hang up.
+        while !server.connections.lock().unwrap().is_empty() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        server.shutdown().unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_allocate() {
+        let dir = temp_dir("allocate");
+        let lease_path = dir.join("tso.lease");
+        let clock = HybridLogicalClock::new().with_time_source(SystemClock);
+        let mut oracle = Oracle::open(clock, &lease_path, Duration::from_secs(1)).unwrap();
+
+        let first = oracle.allocate(5).unwrap();
+        let second = oracle.allocate(1).unwrap();
+        assert_eq!(first.end - first.start, 5);
+        assert!(first.end <= second.start);
+        assert!(oracle.allocate(0).is_err());
+
+        let lease: u64 = fs::read_to_string(&lease_path)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        assert!(lease >= second.end - 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
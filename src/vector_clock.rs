@@ -1,53 +1,119 @@
 use crate::LamportClock;
+use crate::codec::{DecodeError, Varint, read_varint, write_varint};
+use num_traits::{CheckedAdd, CheckedSub};
 use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::ops::Add;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VectorClock<K = usize, V = usize>
 where
     K: Eq + std::hash::Hash + Clone,
-    V: Add<V, Output = V> + From<u8> + Ord + Default + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
 {
     /// Assume there are N processes in the system, all of whom have their own respective vector
     /// clock (say `VC_i` for each process i in {1, ..., N}). Then each clock, `VC_i`, will have
     /// an underlying list of size N, `V_i` such that:
     /// - `V_i[i]` is the number of events that have taken place at process `i`,
     /// - `V_i[j]` is the number of events that process `i` **knows** to have taken place at
-    ///    process `j`, (i.e. that have potentially affected process `i`).
+    ///   process `j`, (i.e. that have potentially affected process `i`).
     ///
     /// Comparing vector timestamps `U` and `V`, we say
     /// - `U == V` if, and only if, `U[i] == V[i]` for each `i` in {1, ..., N},
     /// - `U < V` if, and only if, `U[i] <= V[i]` for each `i` in {1, ..., N} _and_ there exists
-    ///           some `j` such that `U[j] < V[j]`, and
+    ///   some `j` such that `U[j] < V[j]`, and
     /// - `U || V` (are **concurrent**) if neither `U < V` nor `V < U`, i.e. with respect to the
     ///   notion of partial ordering, we'd say `U` and `V` are **not comparable**.
     ///
     /// _(Note that we're conflating a process `p_i` with its index `i` in {1, ..., N}. In practice,
     /// we could have a mapping between the process's index `i` in the vector and its process id.)_
-    clock: HashMap<K, V>,
+    ///
+    /// The map is shared between copies of the clock (e.g. those piggybacked onto messages by
+    /// [`VectorClock::send`]) until one of them is mutated, which is when it's copied.
+    pub(crate) clock: Arc<HashMap<K, V>>,
     /// The key (in the list `clock`) of the process who owns this vector clock struct, i.e.
     /// process `i` would have vector clock `VC_i` from the above description.
-    i: K,
+    pub(crate) i: K,
 }
 
 impl<K, V> VectorClock<K, V>
 where
     K: Eq + std::hash::Hash + Clone,
-    V: Add<V, Output = V> + From<u8> + Ord + Default + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
 {
     /// Constructs a new vector clock for the given process identifier.
     pub fn new(i: K) -> VectorClock<K, V> {
         Self {
-            clock: HashMap::new(),
+            clock: Arc::default(),
             i,
         }
     }
 
+    /// Constructs a vector clock for the given process identifier from its entries. Should a key
+    /// appear more than once, the greatest of its values is kept.
+    pub fn from_entries(i: K, entries: impl IntoIterator<Item = (K, V)>) -> Self {
+        let mut clock = HashMap::new();
+        for (k, v) in entries {
+            let entry = clock.entry(k).or_insert_with(V::default);
+            if *entry < v {
+                *entry = v;
+            }
+        }
+        clock.retain(|_, v| *v != V::default());
+        Self {
+            clock: Arc::new(clock),
+            i,
+        }
+    }
+
+    /// Returns the identifier of the process who owns this clock.
+    pub fn owner(&self) -> &K {
+        &self.i
+    }
+
+    /// Returns the number of entries holding a value other than the default one.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns whether every entry holds the default value, as in a freshly constructed clock.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over the entries holding a value other than the default one, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.clock.iter().filter(|(_, v)| **v != V::default())
+    }
+
+    /// Returns the entries holding a value other than the default one, in ascending order of key.
+    pub fn entries_sorted(&self) -> Vec<(&K, &V)>
+    where
+        K: Ord,
+    {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_unstable_by_key(|(k, _)| *k);
+        entries
+    }
+
     /// Increments the owning process's corresponding value in the vector clock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value overflows `V` (see [`VectorClock::try_bump`]).
     pub fn bump(&mut self) {
-        let value = self.clock.get(&self.i).unwrap_or(&V::default()).clone() + V::from(1);
-        self.clock.insert(self.i.clone(), value);
+        self.try_bump().expect("vector clock entry overflowed");
+    }
+
+    /// Increments the owning process's corresponding value in the vector clock, unless doing so
+    /// would overflow `V`, in which case the clock is left untouched.
+    pub fn try_bump(&mut self) -> Result<(), Overflow> {
+        let value = Self::successor(&self.get(&self.i))?;
+        Arc::make_mut(&mut self.clock).insert(self.i.clone(), value);
+        Ok(())
     }
 
     /// Returns whether this vector clock represents a state that is causal to the state that is
@@ -62,10 +128,11 @@ where
     }
 
     /// Returns whether this vector clock represents a state that is concurrent with the incoming
-    /// vector clock (see documentation for [`VectorClock::happens_before`] for more detail).
+    /// vector clock, i.e. neither happens before the other (see documentation for
+    /// [`VectorClock::happens_before`] for more detail).
     #[inline]
     pub fn is_concurrent_with(&self, other: &Self) -> bool {
-        !self.happens_before(other)
+        self.partial_cmp(other).is_none()
     }
 
     /// When a process intends on sending a message to another process, prepares the sending
@@ -73,12 +140,22 @@ where
     ///
     /// Upholds the vector clock invariant that the value corresponding to the sending process in
     /// the sending process's vector clock must be incremented before being sent.
+    ///
+    /// The returned copy shares its entries with this clock, so sending is cheap regardless of
+    /// the number of processes; this clock copies them on its next update.
     #[inline]
     pub fn send(&mut self) -> Self {
         self.bump();
         self.clone()
     }
 
+    /// Like [`VectorClock::send`], but reports an overflow of the sending process's entry instead
+    /// of panicking.
+    pub fn try_send(&mut self) -> Result<Self, Overflow> {
+        self.try_bump()?;
+        Ok(self.clone())
+    }
+
     /// When a process receives a message from another process, maintains the vector clock
     /// invariant that both:
     /// - each entry of the receiving process's vector clock must be updated to be the max value
@@ -87,13 +164,97 @@ where
     ///   receiving process's vector clock must be incremented.
     #[inline]
     pub fn receive(&mut self, incoming_clock: &Self) {
+        self.try_receive(incoming_clock)
+            .expect("vector clock entry overflowed");
+    }
+
+    /// Like [`VectorClock::receive`], but reports an overflow of the receiving process's entry
+    /// instead of panicking. On overflow, the incoming clock isn't merged either.
+    pub fn try_receive(&mut self, incoming_clock: &Self) -> Result<(), Overflow> {
+        let value = Self::successor(&self.get(&self.i).max(incoming_clock.get(&self.i)))?;
         self.merge(incoming_clock);
-        self.bump();
+        Arc::make_mut(&mut self.clock).insert(self.i.clone(), value);
+        Ok(())
+    }
+
+    fn successor(value: &V) -> Result<V, Overflow> {
+        value.checked_add(&V::from(1)).ok_or(Overflow)
+    }
+
+    /// Returns the least upper bound of the two clocks, i.e. their entry-wise maximum: the clock of
+    /// a process that knows everything either does. The result is owned by this clock's process.
+    pub fn join(&self, other: &Self) -> Self {
+        let mut join = self.clone();
+        join.merge(other);
+        join
+    }
+
+    /// Returns the greatest lower bound of the two clocks, i.e. their entry-wise minimum: what
+    /// both know. The result is owned by this clock's process.
+    pub fn meet(&self, other: &Self) -> Self {
+        let clock = self
+            .clock
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone().min(other.get(k))))
+            .filter(|(_, v)| *v != V::default())
+            .collect();
+        Self {
+            clock: Arc::new(clock),
+            i: self.i.clone(),
+        }
+    }
+
+    /// Folds the last clock of a process that has left the system into this one, so that nothing
+    /// it knew is lost once its entry is pruned (see [`VectorClock::prune`]). Unlike a receive,
+    /// this isn't an event of this clock's process.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `departed` is this clock's own process.
+    pub fn retire(&mut self, departed: &Self) {
+        assert!(departed.i != self.i, "a process can't retire itself");
+        self.merge(departed);
+    }
+
+    /// Drops the entries of retired processes that are at most their value in `frontier`.
+    ///
+    /// The frontier must only hold final entries of retired processes, and be stable, i.e. every
+    /// clock in the system (including those of messages in flight) has caught up with it. Then the
+    /// dropped entries were the same in every clock, so clocks that are pruned alike compare as
+    /// they did before. Clocks received afterwards must be pruned too, or they would reintroduce
+    /// the entries.
+    pub fn prune(&mut self, frontier: &Self) {
+        let retired = |(k, v): (&K, &V)| *k != self.i && *v <= frontier.get(k);
+        if !self.clock.iter().any(retired) {
+            return;
+        }
+        let i = &self.i;
+        Arc::make_mut(&mut self.clock).retain(|k, v| k == i || *v > frontier.get(k));
+    }
+
+    /// Moves the entry of process `from` to process `to` (along with ownership, if this clock is
+    /// `from`'s), e.g. to reuse the id of a retired process whose entry has since been pruned
+    /// everywhere. As long as every clock is aliased alike, they compare as they did before.
+    ///
+    /// Returns `false`, leaving the clock untouched, if `to` already has an entry.
+    pub fn alias(&mut self, from: &K, to: K) -> bool {
+        if self.clock.contains_key(&to) || (self.i == to && self.i != *from) {
+            return false;
+        }
+        if self.clock.contains_key(from) {
+            let clock = Arc::make_mut(&mut self.clock);
+            let v = clock.remove(from).expect("the entry was just looked up");
+            clock.insert(to.clone(), v);
+        }
+        if self.i == *from {
+            self.i = to;
+        }
+        true
     }
 
     /// Fetches the clock's value for a given key, if such an entry exists. Otherwise, returns the
     /// default value.
-    fn get(&self, key: &K) -> V {
+    pub fn get(&self, key: &K) -> V {
         match self.clock.get(key) {
             Some(value) => value.clone(),
             None => V::default(),
@@ -102,7 +263,10 @@ where
 
     /// Merges this vector clock, in place, with the incoming one, taking each merged entry to be
     /// the maximum between the two entries.
-    fn merge(&mut self, other: &VectorClock<K, V>) {
+    pub(crate) fn merge(&mut self, other: &VectorClock<K, V>) {
+        if Arc::ptr_eq(&self.clock, &other.clock) {
+            return;
+        }
         for (k, other_v) in other.clock.iter() {
             // Only overwrite/insert a `key`/`value` pair from other into self if `value` is
             // greater than what we currently have in self corresponding to `key`.
@@ -110,13 +274,132 @@ where
                 Some(self_v) => self_v < other_v,
                 None => true,
             } {
-                self.clock.insert(k.clone(), other_v.clone());
+                Arc::make_mut(&mut self.clock).insert(k.clone(), other_v.clone());
             }
         }
     }
 }
 
-impl LamportClock for VectorClock {
+impl<K, V> VectorClock<K, V>
+where
+    K: Eq + std::hash::Hash + Clone,
+    V: CheckedAdd + CheckedSub + From<u8> + Ord + Default + Clone,
+{
+    /// Returns, for each process, how many of its events this clock knows of but `other` doesn't,
+    /// leaving out processes for which there are none. As with comparisons, missing entries are
+    /// taken to hold the default value. The processes come in arbitrary order.
+    pub fn lag_by_origin(&self, other: &Self) -> Vec<(&K, V)> {
+        self.iter()
+            .filter_map(|(k, v)| {
+                let lag = v.checked_sub(&other.get(k))?;
+                (lag != V::default()).then_some((k, lag))
+            })
+            .collect()
+    }
+
+    /// Returns the events this clock knows of but `other` doesn't, as a clock (owned by this
+    /// clock's process) holding their number per process. It's the default clock if, and only if,
+    /// `self <= other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        let entries = self.lag_by_origin(other);
+        let clock = entries.into_iter().map(|(k, v)| (k.clone(), v)).collect();
+        Self {
+            clock: Arc::new(clock),
+            i: self.i.clone(),
+        }
+    }
+
+    /// Returns the total number of events this clock knows of but `other` doesn't, e.g. how far a
+    /// replica that last reported `other` lags behind. Fails if the total overflows `V`.
+    pub fn lag(&self, other: &Self) -> Result<V, Overflow> {
+        self.lag_by_origin(other)
+            .into_iter()
+            .try_fold(V::default(), |total, (_, lag)| {
+                total.checked_add(&lag).ok_or(Overflow)
+            })
+    }
+}
+
+impl<K, V> VectorClock<K, V>
+where
+    K: Eq + std::hash::Hash + Clone + Ord + Varint,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone + Varint,
+{
+    /// Encodes the clock compactly: the owner, the number of entries, and then each entry in
+    /// ascending order of key, all as varints. Keys are delta-encoded, as the gap from the previous
+    /// key less one, so that clocks over (mostly) contiguous process ids take about a byte per key.
+    /// Entries holding the default value are implied, and left out.
+    pub fn encode(&self) -> Vec<u8> {
+        let entries = self.entries_sorted();
+        let mut buf = Vec::with_capacity(2 + 2 * entries.len());
+        write_varint(&mut buf, self.i);
+        write_varint(&mut buf, entries.len());
+        let mut previous = None;
+        for (k, v) in entries {
+            let k = k.to_u64();
+            write_varint(&mut buf, previous.map_or(k, |previous| k - previous - 1));
+            write_varint(&mut buf, *v);
+            previous = Some(k);
+        }
+        buf
+    }
+
+    /// Decodes a clock produced by [`VectorClock::encode`]. Only the canonical encoding of a clock
+    /// is accepted, so anything decoded re-encodes to the same bytes.
+    pub fn decode(mut buf: &[u8]) -> Result<Self, DecodeError> {
+        let i = read_varint(&mut buf)?;
+        let len: usize = read_varint(&mut buf)?;
+        // Every entry takes at least two bytes, so a corrupt length can't make us allocate more
+        // than the input would allow for.
+        if len > buf.len() / 2 {
+            return Err(DecodeError::Truncated);
+        }
+
+        let mut clock = HashMap::with_capacity(len);
+        let mut previous: Option<u64> = None;
+        for _ in 0..len {
+            let delta: u64 = read_varint(&mut buf)?;
+            let k = match previous {
+                Some(previous) => previous
+                    .checked_add(delta)
+                    .and_then(|k| k.checked_add(1))
+                    .ok_or(DecodeError::Overflow)?,
+                None => delta,
+            };
+            let v: V = read_varint(&mut buf)?;
+            if v == V::default() {
+                return Err(DecodeError::Invalid);
+            }
+            clock.insert(K::from_u64(k).ok_or(DecodeError::Overflow)?, v);
+            previous = Some(k);
+        }
+        if !buf.is_empty() {
+            return Err(DecodeError::Invalid);
+        }
+        Ok(Self {
+            clock: Arc::new(clock),
+            i,
+        })
+    }
+}
+
+/// The error returned when incrementing a vector clock's entry would overflow its value type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overflow;
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "vector clock entry overflowed")
+    }
+}
+
+impl std::error::Error for Overflow {}
+
+impl<K, V> LamportClock for VectorClock<K, V>
+where
+    K: Eq + std::hash::Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
     fn bump(&mut self) {
         VectorClock::bump(self);
     }
@@ -133,7 +416,7 @@ impl LamportClock for VectorClock {
 impl<K, V> PartialEq<Self> for VectorClock<K, V>
 where
     K: Eq + std::hash::Hash + Clone,
-    V: Add<V, Output = V> + From<u8> + Ord + Default + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
 {
     fn eq(&self, other: &Self) -> bool {
         // Returns if for every value in the left clock, the corresponding key's value in the right
@@ -143,7 +426,7 @@ where
         fn subset_eq<K, V>(left: &VectorClock<K, V>, right: &VectorClock<K, V>) -> bool
         where
             K: Eq + std::hash::Hash + Clone,
-            V: Add<Output = V> + From<u8> + Ord + Default + Clone + PartialEq,
+            V: CheckedAdd + From<u8> + Ord + Default + Clone + PartialEq,
         {
             for (k, v) in left.clock.iter() {
                 match right.clock.get(k) {
@@ -163,18 +446,100 @@ where
             true
         }
 
-        if self.clock == other.clock {
+        if Arc::ptr_eq(&self.clock, &other.clock) || self.clock == other.clock {
             return true;
         }
         // A == B if, and only if, both (1) A is a subset of B, and (2) B is a subset of A.
-        subset_eq(self, &other) && subset_eq(&other, self)
+        subset_eq(self, other) && subset_eq(other, self)
+    }
+}
+
+impl<K, V> Eq for VectorClock<K, V>
+where
+    K: Eq + std::hash::Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
+{
+}
+
+/// Hashes the entries holding a value other than the default one, independently of their order
+/// (and of the owner), so that clocks that are equal hash alike.
+impl<K, V> Hash for VectorClock<K, V>
+where
+    K: Eq + std::hash::Hash + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone + Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut len = 0;
+        let mut sum = 0u64;
+        for entry in self.iter() {
+            let mut hasher = DefaultHasher::new();
+            entry.hash(&mut hasher);
+            sum = sum.wrapping_add(hasher.finish());
+            len += 1;
+        }
+        state.write_usize(len);
+        state.write_u64(sum);
+    }
+}
+
+/// Lists the entries holding a value other than the default one, ordered by their keys' debug
+/// representation.
+impl<K, V> fmt::Debug for VectorClock<K, V>
+where
+    K: Eq + std::hash::Hash + Clone + fmt::Debug,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Raw<'a>(&'a str);
+
+        impl fmt::Debug for Raw<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        struct Entries<'a, V>(Vec<(String, &'a V)>);
+
+        impl<V: fmt::Debug> fmt::Debug for Entries<'_, V> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(k, v)| (Raw(k), v)))
+                    .finish()
+            }
+        }
+
+        let mut entries: Vec<_> = self.iter().map(|(k, v)| (format!("{k:?}"), v)).collect();
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        f.debug_struct("VectorClock")
+            .field("owner", &self.i)
+            .field("clock", &Entries(entries))
+            .finish()
+    }
+}
+
+/// Formats the clock as its owner followed by its entries (those holding a value other than the
+/// default one) in ascending order of key, e.g. `p1@{p1: 3, p2: 1}`.
+impl<K, V> fmt::Display for VectorClock<K, V>
+where
+    K: Eq + std::hash::Hash + Clone + Ord + fmt::Display,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{{", self.i)?;
+        for (n, (k, v)) in self.entries_sorted().into_iter().enumerate() {
+            if n > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{k}: {v}")?;
+        }
+        write!(f, "}}")
     }
 }
 
 impl<K, V> PartialOrd for VectorClock<K, V>
 where
     K: Eq + std::hash::Hash + Clone,
-    V: Add<V, Output = V> + From<u8> + Ord + Default + Clone,
+    V: CheckedAdd + From<u8> + Ord + Default + Clone,
 {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         struct HasCmp {
@@ -185,7 +550,7 @@ where
         fn subset_cmp<K, V>(left: &VectorClock<K, V>, right: &VectorClock<K, V>) -> HasCmp
         where
             K: Eq + std::hash::Hash + Clone,
-            V: Add<Output = V> + From<u8> + Ord + Default + Clone + PartialEq,
+            V: CheckedAdd + From<u8> + Ord + Default + Clone + PartialEq,
         {
             let mut has_greater = false;
             let mut has_less = false;
@@ -263,7 +628,11 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::vector_clock::VectorClock;
+    use crate::codec::DecodeError;
+    use crate::vector_clock::{Overflow, VectorClock};
+    use proptest::prelude::*;
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    use std::sync::Arc;
 
     #[test]
     fn test_causality() {
@@ -313,7 +682,8 @@ mod tests {
 
         assert!(vc3.happens_before(&vc1));
         assert!(vc2.is_concurrent_with(&vc1));
-        assert!(vc1.is_concurrent_with(&vc3));
+        // p1 is "after" p3 because of (3.4/1.4).
+        assert!(!vc1.is_concurrent_with(&vc3));
 
         // (1.5 / 2.5)
         let sending_clock = vc1.send();
@@ -328,4 +698,236 @@ mod tests {
         // (2.6)
         vc2.bump();
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut vc1 = VectorClock::<String, u64>::new("p1".to_string());
+        let mut vc2 = VectorClock::new("p2".to_string());
+        vc1.bump();
+        vc2.receive(&vc1.send());
+
+        let json: VectorClock<String, u64> =
+            serde_json::from_str(&serde_json::to_string(&vc2).unwrap()).unwrap();
+        assert_eq!(json, vc2);
+
+        let config = bincode::config::standard();
+        let bytes = bincode::serde::encode_to_vec(&vc2, config).unwrap();
+        let (mut binary, _): (VectorClock<String, u64>, _) =
+            bincode::serde::decode_from_slice(&bytes, config).unwrap();
+        assert_eq!(binary, vc2);
+        // The owner survives the round-trip too.
+        binary.bump();
+        vc2.bump();
+        assert_eq!(binary, vc2);
+    }
+
+    #[test]
+    fn test_copy_on_write() {
+        let mut vc1 = VectorClock::<usize, usize>::new(1);
+        let mut vc2 = VectorClock::<usize, usize>::new(2);
+        vc1.bump();
+        let message = vc1.send();
+        assert!(Arc::ptr_eq(&vc1.clock, &message.clock));
+
+        // Mutating either copy leaves the other as it was.
+        vc1.bump();
+        assert!(!Arc::ptr_eq(&vc1.clock, &message.clock));
+        assert_eq!(message.get(&1), 2);
+        assert!(message.happens_before(&vc1));
+
+        vc2.receive(&message);
+        assert_eq!(vc2.get(&1), 2);
+        assert_eq!(message.get(&2), 0);
+        assert!(vc2.is_concurrent_with(&vc1));
+    }
+
+    #[test]
+    fn test_entries() {
+        let vc = VectorClock::<&str, u64>::from_entries(
+            "p2",
+            [("p10", 4), ("p1", 3), ("p2", 1), ("p1", 2), ("p3", 0)],
+        );
+        assert_eq!(vc.owner(), &"p2");
+        assert_eq!(vc.get(&"p1"), 3);
+        assert_eq!(vc.get(&"p3"), 0);
+        assert_eq!(vc.len(), 3);
+        assert_eq!(
+            vc.entries_sorted(),
+            [(&"p1", &3), (&"p10", &4), (&"p2", &1)]
+        );
+        assert_eq!(vc.to_string(), "p2@{p1: 3, p10: 4, p2: 1}");
+        assert_eq!(
+            format!("{vc:?}"),
+            r#"VectorClock { owner: "p2", clock: {"p1": 3, "p10": 4, "p2": 1} }"#
+        );
+
+        // Equal clocks hash alike, regardless of default-valued entries and of their owner.
+        let hash = |vc: &VectorClock<&str, u64>| {
+            let mut hasher = DefaultHasher::new();
+            vc.hash(&mut hasher);
+            hasher.finish()
+        };
+        let mut other = VectorClock::from_entries("p1", vc.iter().map(|(k, v)| (*k, *v)));
+        Arc::make_mut(&mut other.clock).insert("p4", 0);
+        assert_eq!(other, vc);
+        assert_eq!(hash(&other), hash(&vc));
+        assert!(VectorClock::<&str, u64>::new("p1").is_empty());
+        let set: std::collections::HashSet<_> = [vc, other].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn test_lag() {
+        let [mut p1, mut p2, mut p3] = [1, 2, 3].map(VectorClock::<usize, u8>::new);
+        p1.bump();
+        p2.receive(&p1.send());
+        p1.bump();
+        p3.bump();
+        p1.receive(&p3.send());
+
+        // p1 knows of 4 of its own events and 2 of p3's, of which p2 knows only 2 of p1's.
+        let mut lag = p1.lag_by_origin(&p2);
+        lag.sort();
+        assert_eq!(lag, [(&1, 2), (&3, 2)]);
+        assert_eq!(p1.lag(&p2), Ok(4));
+        assert_eq!(
+            p1.difference(&p2),
+            VectorClock::from_entries(1, [(1, 2), (3, 2)])
+        );
+        // p2's own event is the only one p1 doesn't know of.
+        assert_eq!(p2.lag(&p1), Ok(1));
+        assert_eq!(p1.lag(&p1), Ok(0));
+        assert!(p1.difference(&p1).is_empty());
+
+        let [a, b] = [1, 2].map(|i| VectorClock::<usize, u8>::from_entries(i, [(i, 200)]));
+        assert_eq!(a.join(&b).lag(&VectorClock::new(3)), Err(Overflow));
+    }
+
+    #[test]
+    fn test_lattice() {
+        let [mut p1, mut p2] = [1, 2].map(VectorClock::<usize, usize>::new);
+        p1.bump();
+        p2.receive(&p1.send());
+        p1.bump();
+        let (join, meet) = (p1.join(&p2), p1.meet(&p2));
+        assert_eq!(join.get(&1), 3);
+        assert_eq!(join.get(&2), 1);
+        assert_eq!(meet.get(&1), 2);
+        assert_eq!(meet.get(&2), 0);
+        assert!(p1 <= join && p2 <= join);
+        assert!(meet <= p1 && meet <= p2);
+        assert_eq!(p2.meet(&p1), meet);
+        assert_eq!(p1.meet(&join), p1);
+    }
+
+    #[test]
+    fn test_membership() {
+        let [mut p1, mut p2, mut p3] = [1, 2, 3].map(VectorClock::<usize, usize>::new);
+        p3.bump();
+        p2.receive(&p3.send());
+        p1.bump();
+        // p3 leaves, handing its knowledge to p1.
+        p1.retire(&p3.send());
+        assert!(p3.happens_before(&p1));
+        let mut events = vec![p1.send(), p2.send()];
+        p2.receive(&events[0]);
+        events.push(p2.clone());
+        let ordering = |events: &[VectorClock]| -> Vec<_> {
+            events
+                .iter()
+                .flat_map(|a| events.iter().map(move |b| a.partial_cmp(b)))
+                .collect()
+        };
+        let before = ordering(&events);
+
+        // Once everyone has caught up with p3's last event, its entry can go.
+        let frontier = VectorClock::from_entries(0, [(3, p3.get(&3))]);
+        for clock in events.iter_mut().chain([&mut p1, &mut p2]) {
+            clock.prune(&frontier);
+            assert_eq!(clock.clock.get(&3), None);
+        }
+        assert_eq!(ordering(&events), before);
+        assert!(!events[0].is_concurrent_with(&p1));
+
+        // Renaming a process alike everywhere preserves the order too, and so does reusing the
+        // retired process's id.
+        for clock in events.iter_mut().chain([&mut p1, &mut p2]) {
+            assert!(clock.alias(&2, 4));
+        }
+        assert_eq!(p2.i, 4);
+        assert!(!p2.alias(&1, 4));
+        assert_eq!(ordering(&events), before);
+        let mut p3 = VectorClock::new(3);
+        p3.receive(&p2.send());
+        assert!(events[2].happens_before(&p3));
+        p1.bump();
+        assert!(p1.is_concurrent_with(&p3));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut vc1 = VectorClock::<&str, u8>::new("p1");
+        let mut vc2 = VectorClock::<&str, u8>::new("p2");
+        for _ in 0..u8::MAX {
+            vc1.bump();
+        }
+        assert_eq!(vc1.try_bump(), Err(Overflow));
+        assert_eq!(vc1.try_send(), Err(Overflow));
+        assert_eq!(vc1.get(&"p1"), u8::MAX);
+
+        // A receive that would overflow leaves the receiving clock untouched.
+        vc2.bump();
+        let message = vc2.send();
+        let before = vc1.clone();
+        assert_eq!(vc1.try_receive(&message), Err(Overflow));
+        assert_eq!(vc1, before);
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut vc = VectorClock::<u32, u64>::new(7);
+        vc.bump();
+        vc.receive(&VectorClock::<u32, u64>::new(8).send());
+        Arc::make_mut(&mut vc.clock).insert(300, 0);
+        // Owner, length, then (key delta, value) pairs; the default-valued entry is left out.
+        assert_eq!(vc.encode(), [7, 2, 7, 2, 0, 1]);
+        assert_eq!(VectorClock::decode(&vc.encode()), Ok(vc));
+
+        let decode = VectorClock::<u32, u64>::decode;
+        assert_eq!(decode(&[7, 2, 7, 2, 0]), Err(DecodeError::Truncated));
+        // A length that's larger than the input could possibly hold is rejected upfront.
+        assert_eq!(
+            decode(&[7, 0xff, 0xff, 0xff, 0xff, 0x0f]),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            decode(&[7, 1, 0x80, 0x80, 0x80, 0x80, 0x10, 1]),
+            Err(DecodeError::Overflow)
+        );
+        assert_eq!(decode(&[7, 1, 3, 0]), Err(DecodeError::Invalid));
+        assert_eq!(decode(&[7, 0, 0]), Err(DecodeError::Invalid));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_encode_round_trips(
+            i: u32,
+            entries in prop::collection::hash_map(any::<u32>(), 1..u64::MAX, 0..64),
+        ) {
+            let vc = VectorClock { clock: Arc::new(entries), i };
+            let bytes = vc.encode();
+            let decoded = VectorClock::<u32, u64>::decode(&bytes).unwrap();
+            prop_assert_eq!(&decoded.clock, &vc.clock);
+            prop_assert_eq!(decoded.i, vc.i);
+        }
+
+        #[test]
+        fn prop_decode_never_panics(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+            // Whatever decodes must be canonical.
+            if let Ok(vc) = VectorClock::<u16, u32>::decode(&bytes) {
+                prop_assert_eq!(vc.encode(), bytes);
+            }
+        }
+    }
 }
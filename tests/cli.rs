@@ -0,0 +1,99 @@
+//! Runs the `clock` command-line tool on the logs in `tests/fixtures`.
+#![cfg(feature = "cli")]
+
+use std::process::{Command, Output};
+
+/// Runs `clock` with the given arguments, fixtures given by their file name.
+fn clock(args: &[&str]) -> Output {
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/");
+    let args = args.iter().map(|arg| {
+        if arg.ends_with(".jsonl") {
+            format!("{fixtures}{arg}")
+        } else {
+            arg.to_string()
+        }
+    });
+    Command::new(env!("CARGO_BIN_EXE_clock"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn test_merge() {
+    let output = clock(&["merge", "node-a.jsonl", "node-b.jsonl"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let messages: Vec<_> = stdout(&output)
+        .lines()
+        .map(|line| line.split("\"msg\": ").nth(1).unwrap().to_string())
+        .collect();
+    assert_eq!(
+        messages,
+        [r#""start"}"#, r#""prepare"}"#, r#""commit"}"#, r#""ack"}"#]
+    );
+
+    let output = clock(&["merge", "--field", "missing", "node-a.jsonl"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("node-a.jsonl:1: missing field \"missing\""));
+}
+
+#[test]
+fn test_merge_out_of_order() {
+    let output = clock(&["merge", "out-of-order.jsonl"]);
+    // The entry out of order is reported instead of written out.
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output).lines().count(), 1);
+    assert!(stderr(&output).contains("out-of-order.jsonl:2: timestamp 150:0 precedes"));
+}
+
+#[test]
+fn test_merge_malformed() {
+    let output = clock(&["merge", "malformed.jsonl"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("malformed.jsonl:2: invalid timestamp: \"yesterday\""));
+}
+
+#[test]
+fn test_export() {
+    let output = clock(&["export", "events.jsonl"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let log = stdout(&output);
+    assert!(log.ends_with("q {\"p\": 1, \"q\": 1}\nreceive\n"), "{log}");
+
+    let output = clock(&["export", "--format", "dot", "events.jsonl"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).contains("    e0 -> e1 [style=dashed, constraint=false];\n"));
+}
+
+#[test]
+fn test_export_errors() {
+    let output = clock(&["export", "malformed.jsonl"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("malformed.jsonl:1: "));
+
+    let output = clock(&["export", "spaced-process.jsonl"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("spaced-process.jsonl:2: invalid process name: \"node 1\""));
+
+    let output = clock(&["export", "duplicate-events.jsonl"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("two events of process p have the same clock entry"));
+
+    for args in [
+        &["export", "--format", "svg", "events.jsonl"][..],
+        &["frobnicate"],
+        &[],
+    ] {
+        let output = clock(args);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stderr(&output).contains("usage: clock merge"));
+    }
+}